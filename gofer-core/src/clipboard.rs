@@ -0,0 +1,61 @@
+// gofer-core/src/clipboard.rs
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// Access to a system clipboard.
+///
+/// Backends are polled: the caller compares [`change_token`] with the last
+/// value it saw and only reads the text when it differs, the same way the
+/// macOS pasteboard's change count is used. Backends that are told about
+/// changes by the system can also wake the caller up early from
+/// [`wait_for_change`].
+///
+/// [`change_token`]: ClipboardBackend::change_token
+/// [`wait_for_change`]: ClipboardBackend::wait_for_change
+pub trait ClipboardBackend {
+    /// Short name for logs, e.g. "X11".
+    fn name(&self) -> &'static str;
+
+    /// The clipboard text, or `None` if the clipboard holds something else.
+    fn read_text(&mut self) -> Result<Option<String>, ClipboardError>;
+
+    /// Replaces the clipboard contents with `text`.
+    fn write_text(&mut self, text: &str) -> Result<(), ClipboardError>;
+
+    /// A value that changes whenever the clipboard contents do, including
+    /// after [`write_text`](ClipboardBackend::write_text).
+    fn change_token(&mut self) -> Result<i64, ClipboardError>;
+
+    /// Waits up to `timeout` for the clipboard to change and returns whether
+    /// it was notified of a change. Backends without change notifications
+    /// just sleep, so the caller falls back to polling.
+    fn wait_for_change(&mut self, timeout: Duration) -> Result<bool, ClipboardError> {
+        std::thread::sleep(timeout);
+        Ok(false)
+    }
+}
+
+#[derive(Debug)]
+pub struct ClipboardError {
+    /// The [`ClipboardBackend::name`] of the failing backend.
+    pub backend: &'static str,
+    pub error: String,
+}
+
+impl ClipboardError {
+    pub fn new(backend: &'static str, error: impl fmt::Display) -> Self {
+        ClipboardError {
+            backend,
+            error: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} clipboard error: {}", self.backend, self.error)
+    }
+}
+
+impl Error for ClipboardError {}
@@ -0,0 +1,273 @@
+// gofer-core/src/config.rs
+use crate::history;
+use crate::logging::{self, LogSink};
+use crate::normalize::KeyNormalization;
+use crate::search;
+use crate::trigger::{self, Gesture, TriggerAction, TriggerSource};
+use log::info;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Name of the settings file in the user mapping directory.
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// User settings, read from `config.toml` in the user mapping directory.
+///
+/// Every setting is optional; a missing file gives the defaults.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub history: HistoryConfig,
+    pub log: LogConfig,
+    pub mappings: MappingsConfig,
+    pub misses: MissConfig,
+    pub search: SearchConfig,
+    pub trigger: TriggerConfig,
+}
+
+/// Settings for loading mapping files.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MappingsConfig {
+    /// CSV file names in order of precedence when several files map the
+    /// same source text. Files not listed come after, in name order.
+    pub priority: Vec<String>,
+    /// How source texts are matched, unless overridden for a file.
+    pub normalize: KeyNormalization,
+    /// Per-file overrides, keyed by CSV file name.
+    pub files: HashMap<String, FileConfig>,
+}
+
+/// Settings for a single mapping file. Unset values fall back to the
+/// `[mappings]` defaults.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub normalize: Option<KeyNormalization>,
+    /// Also look up target values and translate them back to the source.
+    /// Off by default, since not every mapping can be safely inverted.
+    pub bidirectional: bool,
+}
+
+impl MappingsConfig {
+    /// The key normalization used for the mapping file at `path`.
+    pub fn normalization(&self, path: &Path) -> KeyNormalization {
+        self.file(path)
+            .and_then(|file| file.normalize)
+            .unwrap_or(self.normalize)
+    }
+
+    /// Whether the mapping file at `path` can also be looked up by its
+    /// target values.
+    pub fn is_bidirectional(&self, path: &Path) -> bool {
+        self.file(path).is_some_and(|file| file.bidirectional)
+    }
+
+    fn file(&self, path: &Path) -> Option<&FileConfig> {
+        let file_name = path.file_name().and_then(|s| s.to_str())?;
+        self.files.get(file_name)
+    }
+
+    /// The precedence rank of a mapping file; lower ranks win.
+    pub fn rank(&self, path: &Path) -> usize {
+        let file_name = path.file_name().and_then(|s| s.to_str());
+        self.priority
+            .iter()
+            .position(|name| Some(name.as_str()) == file_name)
+            .unwrap_or(self.priority.len())
+    }
+}
+
+/// Settings for the lookup history in the menu.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Number of lookups kept.
+    pub size: usize,
+    /// Keep the history across restarts.
+    pub persist: bool,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            size: history::DEFAULT_SIZE,
+            persist: true,
+        }
+    }
+}
+
+/// Settings for the application log. They take effect on restart.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Which records are logged, as a level or `RUST_LOG`-style directives
+    /// such as `"info,gofer_core::watch=debug"`. `$RUST_LOG` overrides it.
+    pub level: String,
+    /// Where records go; unset for the platform's defaults.
+    pub sinks: Option<Vec<LogSink>>,
+    /// Size at which the log file is rotated, in kilobytes.
+    pub max_size_kb: u64,
+    /// Number of rotated log files kept.
+    pub max_files: usize,
+}
+
+impl LogConfig {
+    /// The configured sinks, or the platform's defaults.
+    pub fn sinks(&self) -> Vec<LogSink> {
+        self.sinks.clone().unwrap_or_else(logging::default_sinks)
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: "debug".to_string(),
+            sinks: None,
+            max_size_kb: 1024,
+            max_files: 3,
+        }
+    }
+}
+
+/// Settings for lookups that find no mapping.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MissConfig {
+    /// Show a "No mapping found" notification.
+    pub notify: bool,
+    /// Append misses to the miss log in the user mapping directory. Off by
+    /// default, since copied texts can be passwords or other secrets.
+    pub log: bool,
+    /// Maximum number of misses exported as a CSV skeleton.
+    pub export_limit: usize,
+}
+
+impl Default for MissConfig {
+    fn default() -> Self {
+        MissConfig {
+            notify: true,
+            log: false,
+            export_limit: 100,
+        }
+    }
+}
+
+/// Settings for the search window.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    /// Maximum number of results shown.
+    pub limit: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            limit: search::DEFAULT_LIMIT,
+        }
+    }
+}
+
+/// Settings for the copy gestures, or the selection, that trigger a lookup.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TriggerConfig {
+    /// Maximum time between two copies of the same text, in milliseconds.
+    pub window_ms: u64,
+    /// What copying the same text twice does.
+    pub double_copy: TriggerAction,
+    /// What copying the same text three times does.
+    pub triple_copy: TriggerAction,
+    /// Whether to watch the clipboard or the selection.
+    pub source: TriggerSource,
+    /// How long a selection must stay unchanged, in milliseconds.
+    pub dwell_ms: u64,
+}
+
+impl TriggerConfig {
+    /// The action for a sequence of `copies` copies.
+    pub fn action(&self, copies: u32) -> TriggerAction {
+        match copies {
+            2 => self.double_copy,
+            3 => self.triple_copy,
+            _ => TriggerAction::None,
+        }
+    }
+
+    /// The gesture the detector should look for. A double copy only has to
+    /// wait out the window when a triple copy does something.
+    pub fn gesture(&self) -> Gesture {
+        Gesture {
+            window: Duration::from_millis(self.window_ms),
+            max_copies: if self.triple_copy == TriggerAction::None {
+                2
+            } else {
+                3
+            },
+        }
+    }
+
+    /// How long a selection must stay unchanged before it is looked up.
+    pub fn dwell(&self) -> Duration {
+        Duration::from_millis(self.dwell_ms)
+    }
+}
+
+impl Default for TriggerConfig {
+    fn default() -> Self {
+        TriggerConfig {
+            window_ms: trigger::DOUBLE_COPY_WINDOW.as_millis() as u64,
+            double_copy: TriggerAction::Notify,
+            triple_copy: TriggerAction::None,
+            source: TriggerSource::Clipboard,
+            dwell_ms: trigger::SELECTION_DWELL.as_millis() as u64,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error in {:?}: {}", self.path, self.error)
+    }
+}
+
+impl Error for ConfigError {}
+
+impl Config {
+    /// Reads the settings from `path`, or the defaults if it does not exist.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        info!("Loading config from: {:?}", path);
+        let contents = fs::read_to_string(path).map_err(|e| ConfigError {
+            path: path.to_path_buf(),
+            error: format!("Failed to read config: {}", e),
+        })?;
+        toml::from_str(&contents).map_err(|e| ConfigError {
+            path: path.to_path_buf(),
+            error: format!("Failed to parse config: {}", e),
+        })
+    }
+
+    /// Reads `config.toml` from the user mapping directory, if there is one.
+    pub fn load_from_dir(user_dir: Option<&Path>) -> Result<Self, ConfigError> {
+        match user_dir {
+            Some(dir) => Config::load(&dir.join(CONFIG_FILE_NAME)),
+            None => Ok(Config::default()),
+        }
+    }
+}
@@ -0,0 +1,917 @@
+use crate::config::MappingsConfig;
+use crate::normalize::KeyNormalization;
+use crate::search::SearchIndex;
+use csv::ReaderBuilder;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// A source text and its translations into every target column of its file.
+#[derive(Debug)]
+pub struct Mapping {
+    /// The source text as written in the file, trimmed.
+    pub source: String,
+    pub source_name: String,
+    /// Translations in the column order of the file, without empty cells.
+    pub translations: Vec<Translation>,
+    /// The CSV file this mapping was read from.
+    pub path: PathBuf,
+    /// 1-based line of the row in `path`.
+    pub line: u64,
+    pub origin: MappingOrigin,
+    /// Whether this mapping reads a row backwards, from one of its target
+    /// columns to the source column. Only built for bidirectional files.
+    pub reversed: bool,
+}
+
+/// Where a mapping file lives. User files take precedence over app files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MappingOrigin {
+    User,
+    App,
+}
+
+/// One target column's value for a [`Mapping`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Translation {
+    pub target_name: String,
+    pub value: String,
+}
+
+impl Mapping {
+    /// Returns the translation into the column named `target_name`.
+    pub fn target(&self, target_name: &str) -> Option<&str> {
+        self.translations
+            .iter()
+            .find(|t| t.target_name == target_name)
+            .map(|t| t.value.as_str())
+    }
+
+    /// Where this mapping was read from, e.g. "glossary.csv:42".
+    pub fn location(&self) -> String {
+        format_location(&self.path, self.line)
+    }
+
+    /// Reads the row backwards from the translation at `index`: that value
+    /// becomes the source, followed by the original source and the other
+    /// translations.
+    fn reversed(&self, index: usize) -> Mapping {
+        let pivot = &self.translations[index];
+        let translations = std::iter::once(Translation {
+            target_name: self.source_name.clone(),
+            value: self.source.clone(),
+        })
+        .chain(
+            self.translations
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != index)
+                .map(|(_, t)| t.clone()),
+        )
+        .collect();
+
+        Mapping {
+            source: pivot.value.clone(),
+            source_name: pivot.target_name.clone(),
+            translations,
+            path: self.path.clone(),
+            line: self.line,
+            origin: self.origin,
+            reversed: true,
+        }
+    }
+
+    /// The names of the target columns this mapping has a value for.
+    pub fn target_names(&self) -> Vec<&str> {
+        self.translations
+            .iter()
+            .map(|t| t.target_name.as_str())
+            .collect()
+    }
+}
+
+/// Formats a file name and line as "glossary.csv:42".
+pub fn format_location(path: &Path, line: u64) -> String {
+    let file_name = path.file_name().unwrap_or(path.as_os_str());
+    format!("{}:{}", file_name.to_string_lossy(), line)
+}
+
+/// A problem found while loading mappings.
+///
+/// Loading never stops at the first problem: bad rows and files are skipped
+/// and reported, and everything else is still loaded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MappingDiagnostic {
+    pub path: PathBuf,
+    /// 1-based line number, when the problem is tied to a line.
+    pub line: Option<u64>,
+    /// 1-based column number, when the problem is tied to a field.
+    pub column: Option<usize>,
+    pub reason: String,
+}
+
+impl MappingDiagnostic {
+    pub(crate) fn new(
+        path: &Path,
+        line: Option<u64>,
+        column: Option<usize>,
+        reason: String,
+    ) -> Self {
+        MappingDiagnostic {
+            path: path.to_path_buf(),
+            line,
+            column,
+            reason,
+        }
+    }
+
+    pub(crate) fn from_csv_error(path: &Path, context: &str, e: &csv::Error) -> Self {
+        let line = e.position().map(|pos| pos.line());
+        let column = match e.kind() {
+            csv::ErrorKind::Utf8 { err, .. } => Some(err.field() + 1),
+            _ => None,
+        };
+        MappingDiagnostic::new(path, line, column, format!("{}: {}", context, e))
+    }
+}
+
+impl fmt::Display for MappingDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// Summarizes diagnostics for display, e.g. "3 problems in 2 files".
+pub fn summarize_diagnostics(diagnostics: &[MappingDiagnostic]) -> String {
+    let files: HashSet<&Path> = diagnostics.iter().map(|d| d.path.as_path()).collect();
+    format!(
+        "{} problem{} in {} file{}",
+        diagnostics.len(),
+        if diagnostics.len() == 1 { "" } else { "s" },
+        files.len(),
+        if files.len() == 1 { "" } else { "s" },
+    )
+}
+
+/// All mappings loaded from a set of directories, indexed by source text.
+///
+/// When several files map the same source text every candidate is kept,
+/// ordered by precedence: user files before app files, then by the
+/// configured file priority, then by file name, then by line.
+///
+/// Each file's source texts are indexed under its configured
+/// [`KeyNormalization`], and lookups normalize the query the same way.
+/// Files marked bidirectional are also indexed by their target values, so
+/// a lookup can translate back to the source; such reversed candidates come
+/// after all regular ones.
+///
+/// A store is an ordinary value: it can be built, queried and dropped freely.
+/// Use [`SharedMappingStore`] to swap in a freshly loaded store while readers
+/// keep using the previous one.
+#[derive(Debug, Default)]
+pub struct MappingStore {
+    /// Every mapping in order of precedence, followed by the reversed
+    /// mappings of bidirectional files.
+    mappings: Vec<Mapping>,
+    /// Number of mappings read as written, before the reversed ones.
+    forward_len: usize,
+    /// Positions in `mappings` by normalized source text, one index for each
+    /// normalization in use.
+    index: Vec<(KeyNormalization, HashMap<String, Vec<usize>>)>,
+    /// Prefilter for fuzzy search over the forward mappings.
+    search_index: SearchIndex,
+}
+
+impl MappingStore {
+    /// Loads every CSV file in `user_dir` and `app_dir`.
+    ///
+    /// Rows and files that cannot be loaded are skipped and returned as
+    /// diagnostics alongside the partial store.
+    pub fn load(
+        app_dir: &Path,
+        user_dir: Option<&Path>,
+        config: &MappingsConfig,
+    ) -> (Self, Vec<MappingDiagnostic>) {
+        let mut all_mappings = Vec::new();
+        let mut diagnostics = Vec::new();
+
+        // Load in order of precedence, so the candidates for each source
+        // text end up sorted with the primary one first
+        if let Some(user_dir) = user_dir {
+            info!("Loading user mappings from: {:?}", user_dir);
+            load_directory_mappings(
+                user_dir,
+                MappingOrigin::User,
+                config,
+                &mut all_mappings,
+                &mut diagnostics,
+            );
+        }
+
+        info!("Loading app mappings from: {:?}", app_dir);
+        load_directory_mappings(
+            app_dir,
+            MappingOrigin::App,
+            config,
+            &mut all_mappings,
+            &mut diagnostics,
+        );
+
+        info!(
+            "Loaded {} total mappings with {} problems",
+            all_mappings.len(),
+            diagnostics.len()
+        );
+        let store = MappingStore::from_mappings(all_mappings, config);
+        (store, diagnostics)
+    }
+
+    /// Builds a store from mappings given in order of precedence.
+    pub fn from_mappings(mut mappings: Vec<Mapping>, config: &MappingsConfig) -> Self {
+        let forward_len = mappings.len();
+        let reversed: Vec<Mapping> = mappings
+            .iter()
+            .filter(|mapping| config.is_bidirectional(&mapping.path))
+            .flat_map(|mapping| (0..mapping.translations.len()).map(|i| mapping.reversed(i)))
+            .collect();
+        mappings.extend(reversed);
+
+        let mut index: Vec<(KeyNormalization, HashMap<String, Vec<usize>>)> = Vec::new();
+
+        for (position, mapping) in mappings.iter().enumerate() {
+            let normalization = config.normalization(&mapping.path);
+            let keys = match index.iter_mut().find(|(n, _)| *n == normalization) {
+                Some((_, keys)) => keys,
+                None => {
+                    index.push((normalization, HashMap::new()));
+                    &mut index.last_mut().unwrap().1
+                }
+            };
+            keys.entry(normalization.apply(&mapping.source))
+                .or_default()
+                .push(position);
+        }
+
+        let search_index = SearchIndex::build(&mappings[..forward_len]);
+
+        MappingStore {
+            mappings,
+            forward_len,
+            index,
+            search_index,
+        }
+    }
+
+    /// Returns the primary mapping for `source`.
+    pub fn find_target(&self, source: &str) -> Option<&Mapping> {
+        self.find_candidates(source).first().copied()
+    }
+
+    /// Returns every mapping for `source`, primary first. Reversed mappings
+    /// of bidirectional files come after all regular ones.
+    pub fn find_candidates(&self, source: &str) -> Vec<&Mapping> {
+        let mut positions: Vec<usize> = self
+            .index
+            .iter()
+            .filter_map(|(normalization, keys)| keys.get(&normalization.apply(source)))
+            .flatten()
+            .copied()
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+        positions.into_iter().map(|p| &self.mappings[p]).collect()
+    }
+
+    /// Every mapping as written in its file, in order of precedence.
+    pub fn get_all_mappings(&self) -> &[Mapping] {
+        &self.mappings[..self.forward_len]
+    }
+
+    /// The fuzzy search prefilter, built when the store was loaded.
+    pub fn search_index(&self) -> &SearchIndex {
+        &self.search_index
+    }
+
+    pub fn len(&self) -> usize {
+        self.forward_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.forward_len == 0
+    }
+}
+
+/// A [`MappingStore`] that can be replaced atomically at runtime.
+///
+/// Readers take a cheap `Arc` snapshot with [`SharedMappingStore::current`]
+/// and are unaffected by a concurrent [`SharedMappingStore::replace`].
+#[derive(Debug, Default)]
+pub struct SharedMappingStore {
+    current: RwLock<Arc<MappingStore>>,
+}
+
+impl SharedMappingStore {
+    pub fn new(store: MappingStore) -> Self {
+        SharedMappingStore {
+            current: RwLock::new(Arc::new(store)),
+        }
+    }
+
+    /// Returns a snapshot of the active store.
+    pub fn current(&self) -> Arc<MappingStore> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    /// Makes `store` the active store and returns the previous one.
+    pub fn replace(&self, store: MappingStore) -> Arc<MappingStore> {
+        std::mem::replace(&mut *self.current.write().unwrap(), Arc::new(store))
+    }
+
+    /// Loads a fresh store from the given directories and makes it active.
+    ///
+    /// Returns the problems found while loading; whatever could be loaded is
+    /// still made active.
+    pub fn reload(
+        &self,
+        app_dir: &Path,
+        user_dir: Option<&Path>,
+        config: &MappingsConfig,
+    ) -> Vec<MappingDiagnostic> {
+        let (store, diagnostics) = MappingStore::load(app_dir, user_dir, config);
+        self.replace(store);
+        diagnostics
+    }
+}
+
+fn load_directory_mappings(
+    dir: &Path,
+    origin: MappingOrigin,
+    config: &MappingsConfig,
+    mappings: &mut Vec<Mapping>,
+    diagnostics: &mut Vec<MappingDiagnostic>,
+) {
+    for path in mapping_files(dir, config, diagnostics) {
+        load_file_mappings(&path, origin, mappings, diagnostics);
+    }
+}
+
+/// The CSV files in `dir` in the order they are loaded. A missing directory
+/// has no files.
+pub(crate) fn mapping_files(
+    dir: &Path,
+    config: &MappingsConfig,
+    diagnostics: &mut Vec<MappingDiagnostic>,
+) -> Vec<PathBuf> {
+    if !dir.exists() {
+        return Vec::new();
+    }
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            diagnostics.push(MappingDiagnostic::new(
+                dir,
+                None,
+                None,
+                format!("Failed to read directory: {}", e),
+            ));
+            return Vec::new();
+        }
+    };
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                diagnostics.push(MappingDiagnostic::new(
+                    dir,
+                    None,
+                    None,
+                    format!("Failed to read directory entry: {}", e),
+                ));
+                continue;
+            }
+        };
+
+        if path.extension().and_then(|s| s.to_str()) == Some("csv") {
+            paths.push(path);
+        }
+    }
+
+    // `read_dir` order is unspecified, so sort by priority and then by name
+    paths.sort_by(|a, b| config.rank(a).cmp(&config.rank(b)).then_with(|| a.cmp(b)));
+    paths
+}
+
+fn load_file_mappings(
+    path: &Path,
+    origin: MappingOrigin,
+    mappings: &mut Vec<Mapping>,
+    diagnostics: &mut Vec<MappingDiagnostic>,
+) {
+    info!("Loading CSV file: {:?}", path);
+
+    // Rows with a different number of fields than the header are checked
+    // below, so they can be reported and skipped one by one
+    let mut reader = match ReaderBuilder::new().flexible(true).from_path(path) {
+        Ok(reader) => reader,
+        Err(e) => {
+            diagnostics.push(MappingDiagnostic::from_csv_error(
+                path,
+                "Failed to open CSV",
+                &e,
+            ));
+            return;
+        }
+    };
+
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => {
+            diagnostics.push(MappingDiagnostic::from_csv_error(
+                path,
+                "Failed to read headers",
+                &e,
+            ));
+            return;
+        }
+    };
+
+    if headers.len() < 2 {
+        diagnostics.push(MappingDiagnostic::new(
+            path,
+            Some(1),
+            None,
+            "CSV must have at least 2 columns".to_string(),
+        ));
+        return;
+    }
+
+    let source_name = headers[0].trim().to_string();
+    let target_names: Vec<String> = headers
+        .iter()
+        .skip(1)
+        .map(|h| h.trim().to_string())
+        .collect();
+
+    info!(
+        "Processing mappings from '{}' to '{}'",
+        source_name,
+        target_names.join("', '")
+    );
+
+    for result in reader.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                diagnostics.push(MappingDiagnostic::from_csv_error(
+                    path,
+                    "Failed to parse row",
+                    &e,
+                ));
+                continue;
+            }
+        };
+        let line_number = record.position().map_or(0, |pos| pos.line());
+
+        if record.len() < 2 {
+            diagnostics.push(MappingDiagnostic::new(
+                path,
+                Some(line_number),
+                Some(record.len() + 1),
+                "Row has fewer than 2 columns".to_string(),
+            ));
+            continue;
+        }
+
+        if record.len() > headers.len() {
+            diagnostics.push(MappingDiagnostic::new(
+                path,
+                Some(line_number),
+                Some(headers.len() + 1),
+                format!(
+                    "Row has {} columns but the header has {}; extra columns are ignored",
+                    record.len(),
+                    headers.len()
+                ),
+            ));
+        }
+
+        let source = record[0].trim();
+
+        // Every non-empty cell after the source is a translation; missing
+        // trailing cells are treated as empty
+        let translations: Vec<Translation> = target_names
+            .iter()
+            .zip(record.iter().skip(1))
+            .map(|(target_name, value)| (target_name, value.trim()))
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(target_name, value)| Translation {
+                target_name: target_name.clone(),
+                value: value.to_string(),
+            })
+            .collect();
+
+        // Skip empty mappings
+        if source.is_empty() || translations.is_empty() {
+            info!("Skipping empty mapping at line {}", line_number);
+            continue;
+        }
+
+        mappings.push(Mapping {
+            source: source.to_string(),
+            source_name: source_name.clone(),
+            translations,
+            path: path.to_path_buf(),
+            line: line_number,
+            origin,
+            reversed: false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Dirs;
+    use std::thread;
+
+    /// A mapping from `source` to a single French translation.
+    fn mapping(source: &str, translation: &str, file_name: &str, line: u64) -> Mapping {
+        Mapping {
+            source: source.to_string(),
+            source_name: "en".to_string(),
+            translations: vec![Translation {
+                target_name: "fr".to_string(),
+                value: translation.to_string(),
+            }],
+            path: PathBuf::from(file_name),
+            line,
+            origin: MappingOrigin::User,
+            reversed: false,
+        }
+    }
+
+    fn store(pairs: &[(&str, &str)]) -> MappingStore {
+        let mappings = pairs
+            .iter()
+            .enumerate()
+            .map(|(i, (source, translation))| {
+                mapping(source, translation, "words.csv", i as u64 + 2)
+            })
+            .collect();
+        MappingStore::from_mappings(mappings, &MappingsConfig::default())
+    }
+
+    fn load(dirs: &Dirs, config: &MappingsConfig) -> (MappingStore, Vec<MappingDiagnostic>) {
+        MappingStore::load(&dirs.app_dir(), Some(&dirs.user_dir()), config)
+    }
+
+    fn translation(store: &MappingStore, source: &str) -> Option<String> {
+        store
+            .find_target(source)
+            .map(|mapping| mapping.translations[0].value.clone())
+    }
+
+    #[test]
+    fn readers_keep_their_snapshot_across_a_replace() {
+        let shared = SharedMappingStore::new(store(&[("hello", "bonjour")]));
+        let before = shared.current();
+
+        let previous = shared.replace(store(&[("goodbye", "au revoir")]));
+        assert!(Arc::ptr_eq(&before, &previous));
+        drop(previous);
+
+        // The old snapshot still answers as before
+        assert_eq!(translation(&before, "hello").as_deref(), Some("bonjour"));
+        assert_eq!(translation(&before, "goodbye"), None);
+
+        let after = shared.current();
+        assert_eq!(translation(&after, "hello"), None);
+        assert_eq!(translation(&after, "goodbye").as_deref(), Some("au revoir"));
+    }
+
+    #[test]
+    fn concurrent_readers_always_see_a_whole_store() {
+        let shared = Arc::new(SharedMappingStore::new(store(&[("hello", "bonjour")])));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        let snapshot = shared.current();
+                        // Each store maps exactly one of the two texts
+                        let hello = translation(&snapshot, "hello").is_some();
+                        let goodbye = translation(&snapshot, "goodbye").is_some();
+                        assert!(hello != goodbye);
+                    }
+                })
+            })
+            .collect();
+
+        for i in 0..200 {
+            if i % 2 == 0 {
+                shared.replace(store(&[("goodbye", "au revoir")]));
+            } else {
+                shared.replace(store(&[("hello", "bonjour")]));
+            }
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn malformed_rows_are_reported_and_skipped() {
+        let dirs = Dirs::new("malformed");
+        let mut contents = b"en,fr\nhello,bonjour\nlonely\n".to_vec();
+        contents.extend_from_slice(b"bad,\xff\xfe\ngoodbye,au revoir\n");
+        dirs.write("user", "words.csv", contents);
+
+        let (store, diagnostics) = load(&dirs, &MappingsConfig::default());
+        let path = dirs.path("user", "words.csv");
+        let problems: Vec<(Option<u64>, Option<usize>)> =
+            diagnostics.iter().map(|d| (d.line, d.column)).collect();
+        assert_eq!(problems, vec![(Some(3), Some(2)), (Some(4), Some(2))]);
+        assert!(diagnostics.iter().all(|d| d.path == path));
+        assert_eq!(diagnostics[0].reason, "Row has fewer than 2 columns");
+        assert!(diagnostics[1].reason.starts_with("Failed to parse row"));
+
+        // The rows around the bad ones are still loaded
+        assert_eq!(store.len(), 2);
+        assert_eq!(translation(&store, "hello").as_deref(), Some("bonjour"));
+        assert_eq!(translation(&store, "goodbye").as_deref(), Some("au revoir"));
+    }
+
+    #[test]
+    fn unreadable_files_do_not_stop_the_others() {
+        let dirs = Dirs::new("unreadable");
+        // A directory with a CSV name cannot be read as a file
+        fs::create_dir(dirs.path("app", "broken.csv")).unwrap();
+        dirs.write("app", "narrow.csv", "en\nhello\n");
+        dirs.write("user", "words.csv", "en,fr\nhello,bonjour\n");
+
+        let (store, diagnostics) = load(&dirs, &MappingsConfig::default());
+        let problems: Vec<(PathBuf, Option<u64>)> = diagnostics
+            .iter()
+            .map(|d| (d.path.clone(), d.line))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                (dirs.path("app", "broken.csv"), None),
+                (dirs.path("app", "narrow.csv"), Some(1)),
+            ]
+        );
+        assert!(diagnostics[0].reason.starts_with("Failed to read headers"));
+        assert_eq!(diagnostics[1].reason, "CSV must have at least 2 columns");
+
+        assert_eq!(store.len(), 1);
+        assert_eq!(translation(&store, "hello").as_deref(), Some("bonjour"));
+    }
+
+    #[test]
+    fn reload_makes_the_partial_store_active() {
+        let dirs = Dirs::new("reload");
+        dirs.write("user", "words.csv", "en,fr\nhello,bonjour\nlonely\n");
+
+        let shared = SharedMappingStore::default();
+        let diagnostics = shared.reload(
+            &dirs.app_dir(),
+            Some(&dirs.user_dir()),
+            &MappingsConfig::default(),
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            translation(&shared.current(), "hello").as_deref(),
+            Some("bonjour")
+        );
+    }
+
+    #[test]
+    fn diagnostics_are_summarized_by_file() {
+        let diagnostic = |file_name: &str| {
+            MappingDiagnostic::new(Path::new(file_name), Some(2), None, "bad".to_string())
+        };
+        assert_eq!(
+            summarize_diagnostics(&[
+                diagnostic("a.csv"),
+                diagnostic("a.csv"),
+                diagnostic("b.csv")
+            ]),
+            "3 problems in 2 files"
+        );
+        assert_eq!(
+            summarize_diagnostics(&[diagnostic("a.csv")]),
+            "1 problem in 1 file"
+        );
+        assert_eq!(diagnostic("a.csv").to_string(), "a.csv:2: bad");
+        let with_column =
+            MappingDiagnostic::new(Path::new("a.csv"), Some(4), Some(3), "bad".to_string());
+        assert_eq!(with_column.to_string(), "a.csv:4:3: bad");
+    }
+
+    #[test]
+    fn every_target_column_is_a_translation() {
+        let dirs = Dirs::new("columns");
+        dirs.write(
+            "user",
+            "words.csv",
+            " en , fr ,de,es\nhello, bonjour ,hallo,hola\ncat,chat,,\ndog,chien\n",
+        );
+
+        let (store, diagnostics) = load(&dirs, &MappingsConfig::default());
+        assert!(diagnostics.is_empty());
+
+        let hello = store.find_target("hello").unwrap();
+        assert_eq!(hello.source_name, "en");
+        assert_eq!(hello.target_names(), vec!["fr", "de", "es"]);
+        assert_eq!(hello.target("fr"), Some("bonjour"));
+        assert_eq!(hello.target("es"), Some("hola"));
+
+        // Empty and missing trailing cells are left out
+        let cat = store.find_target("cat").unwrap();
+        assert_eq!(cat.target_names(), vec!["fr"]);
+        assert_eq!(cat.target("de"), None);
+        assert_eq!(store.find_target("dog").unwrap().target_names(), vec!["fr"]);
+    }
+
+    #[test]
+    fn extra_cells_are_reported_and_ignored() {
+        let dirs = Dirs::new("wide");
+        dirs.write(
+            "user",
+            "words.csv",
+            "en,fr\nhello,bonjour,hallo\n,vide\nempty,\n",
+        );
+
+        let (store, diagnostics) = load(&dirs, &MappingsConfig::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            (diagnostics[0].line, diagnostics[0].column),
+            (Some(2), Some(3))
+        );
+        assert_eq!(
+            diagnostics[0].reason,
+            "Row has 3 columns but the header has 2; extra columns are ignored"
+        );
+
+        let hello = store.find_target("hello").unwrap();
+        assert_eq!(hello.target_names(), vec!["fr"]);
+        // Rows without a source or any translation are skipped
+        assert_eq!(store.len(), 1);
+    }
+
+    /// Where each candidate for `source` comes from, in order.
+    fn provenance(
+        dirs: &Dirs,
+        store: &MappingStore,
+        source: &str,
+    ) -> Vec<(MappingOrigin, String, u64)> {
+        store
+            .find_candidates(source)
+            .iter()
+            .map(|m| {
+                let path = dirs.relative(&m.path);
+                (m.origin, path.display().to_string(), m.line)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn duplicates_are_kept_in_order_of_precedence() {
+        let dirs = Dirs::new("precedence");
+        dirs.write("app", "a.csv", "en,fr\nhello,app a\n");
+        dirs.write("app", "z.csv", "en,fr\nhello,app z\n");
+        dirs.write("user", "b.csv", "en,fr\nhello,user b\n");
+        dirs.write(
+            "user",
+            "c.csv",
+            "en,fr\nbye,salut\nhello,user c\nhello,user c again\n",
+        );
+
+        // User files first, then app files, each by name and line
+        let (store, _) = load(&dirs, &MappingsConfig::default());
+        assert_eq!(
+            provenance(&dirs, &store, "hello"),
+            vec![
+                (MappingOrigin::User, "user/b.csv".to_string(), 2),
+                (MappingOrigin::User, "user/c.csv".to_string(), 3),
+                (MappingOrigin::User, "user/c.csv".to_string(), 4),
+                (MappingOrigin::App, "app/a.csv".to_string(), 2),
+                (MappingOrigin::App, "app/z.csv".to_string(), 2),
+            ]
+        );
+        assert_eq!(translation(&store, "hello").as_deref(), Some("user b"));
+
+        // The configured priority comes before the name, but never lets an
+        // app file win over a user file
+        let config = MappingsConfig {
+            priority: vec!["z.csv".to_string(), "c.csv".to_string()],
+            ..MappingsConfig::default()
+        };
+        let (store, _) = load(&dirs, &config);
+        assert_eq!(
+            provenance(&dirs, &store, "hello"),
+            vec![
+                (MappingOrigin::User, "user/c.csv".to_string(), 3),
+                (MappingOrigin::User, "user/c.csv".to_string(), 4),
+                (MappingOrigin::User, "user/b.csv".to_string(), 2),
+                (MappingOrigin::App, "app/z.csv".to_string(), 2),
+                (MappingOrigin::App, "app/a.csv".to_string(), 2),
+            ]
+        );
+        let hello = store.find_target("hello").unwrap();
+        assert_eq!(hello.translations[0].value, "user c");
+        assert_eq!(hello.location(), "c.csv:3");
+        assert_eq!(store.len(), 6);
+    }
+
+    #[test]
+    fn lookups_normalize_like_the_file_was_loaded() {
+        let dirs = Dirs::new("normalize");
+        dirs.write(
+            "user",
+            "loose.csv",
+            "en,fr\n  Café Crème ,coffee with cream\n",
+        );
+        dirs.write("user", "strict.csv", "en,fr\nThé,tea\n");
+
+        let mut config = MappingsConfig::default();
+        config.files.insert(
+            "loose.csv".to_string(),
+            toml::from_str("normalize = [\"trim\", \"case-fold\", \"strip-diacritics\"]").unwrap(),
+        );
+        let (store, _) = load(&dirs, &config);
+
+        // The loose file answers any spelling that normalizes to its key
+        for query in ["café crème", "CAFE CREME\n", "Cafe\u{301} Cre\u{300}me"] {
+            assert_eq!(
+                translation(&store, query).as_deref(),
+                Some("coffee with cream"),
+                "{:?}",
+                query
+            );
+        }
+        // The other file keeps the default, trimming only
+        assert_eq!(translation(&store, " Thé\n").as_deref(), Some("tea"));
+        assert_eq!(translation(&store, "thé"), None);
+        assert_eq!(translation(&store, "The"), None);
+    }
+
+    #[test]
+    fn bidirectional_files_translate_back() {
+        let dirs = Dirs::new("bidirectional");
+        dirs.write("user", "greetings.csv", "en,fr,de\nhello,Bonjour,Hallo\n");
+        dirs.write("user", "oneway.csv", "en,fr\ncat,chat\n");
+        dirs.write("app", "french.csv", "fr,en\nbonjour,good day\n");
+
+        let mut config = MappingsConfig::default();
+        config.files.insert(
+            "greetings.csv".to_string(),
+            toml::from_str("bidirectional = true\nnormalize = [\"trim\", \"case-fold\"]").unwrap(),
+        );
+        let (store, _) = load(&dirs, &config);
+
+        // Each target column answers with the source and the other targets
+        let hallo = store.find_target("hallo").unwrap();
+        assert!(hallo.reversed);
+        assert_eq!(hallo.source, "Hallo");
+        assert_eq!(hallo.source_name, "de");
+        assert_eq!(hallo.target("en"), Some("hello"));
+        assert_eq!(hallo.target("fr"), Some("Bonjour"));
+        assert_eq!(hallo.location(), "greetings.csv:2");
+
+        // Forward mappings come first, even from an app file
+        let candidates = store.find_candidates("BONJOUR");
+        let found: Vec<(bool, &str)> = candidates
+            .iter()
+            .map(|m| (m.reversed, m.translations[0].value.as_str()))
+            .collect();
+        assert_eq!(found, vec![(true, "hello")]);
+        let candidates = store.find_candidates("bonjour");
+        let found: Vec<(bool, &str)> = candidates
+            .iter()
+            .map(|m| (m.reversed, m.translations[0].value.as_str()))
+            .collect();
+        assert_eq!(found, vec![(false, "good day"), (true, "hello")]);
+
+        // Other files only translate forwards, and reversed mappings are not
+        // listed as mappings of their own
+        assert!(store.find_target("chat").is_none());
+        assert_eq!(store.len(), 3);
+        assert!(store.get_all_mappings().iter().all(|m| !m.reversed));
+    }
+}
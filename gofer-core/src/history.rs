@@ -0,0 +1,369 @@
+// gofer-core/src/history.rs
+use crate::data::{Mapping, Translation};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the history file in the user mapping directory.
+pub const HISTORY_FILE_NAME: &str = "history.jsonl";
+
+/// Number of lookups kept by default.
+pub const DEFAULT_SIZE: usize = 10;
+
+/// A lookup shown in the history menu.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    /// The text that was looked up.
+    pub source: String,
+    /// Name of the mapping's source column.
+    pub source_name: String,
+    pub translations: Vec<Translation>,
+    /// Where the mapping came from, e.g. "colors.csv:3".
+    pub location: String,
+    /// When the lookup happened, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Pinned entries are listed separately and never evicted.
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+impl HistoryEntry {
+    pub fn new(source: &str, mapping: &Mapping, timestamp: u64) -> Self {
+        HistoryEntry {
+            source: source.to_string(),
+            source_name: mapping.source_name.clone(),
+            translations: mapping.translations.clone(),
+            location: mapping.location(),
+            timestamp,
+            pinned: false,
+        }
+    }
+}
+
+/// The most recent lookups, persisted as JSON lines.
+///
+/// There is one entry per source text; looking a text up again moves it to
+/// the top. Up to `max_len` unpinned entries are kept, plus any number of
+/// pinned ones. Entries are addressed by index, which stays valid until the
+/// history is next changed.
+///
+/// New entries are appended to the file; it is rewritten with just the
+/// kept entries once it has grown to twice the history size, and whenever
+/// entries are pinned, unpinned or cleared.
+#[derive(Debug)]
+pub struct History {
+    path: Option<PathBuf>,
+    entries: VecDeque<HistoryEntry>,
+    max_len: usize,
+    lines_written: usize,
+}
+
+impl History {
+    /// An empty history that is not saved anywhere.
+    pub fn in_memory(max_len: usize) -> Self {
+        History {
+            path: None,
+            entries: VecDeque::new(),
+            max_len,
+            lines_written: 0,
+        }
+    }
+
+    /// Loads the last `max_len` entries from `path`, which is also where new
+    /// entries are saved. A missing file gives an empty history; unreadable
+    /// lines are skipped.
+    pub fn load(path: &Path, max_len: usize) -> io::Result<Self> {
+        let mut history = History::in_memory(max_len);
+        history.path = Some(path.to_path_buf());
+
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(history),
+            Err(e) => return Err(e),
+        };
+        for (line_number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            history.lines_written += 1;
+            match serde_json::from_str::<HistoryEntry>(&line) {
+                Ok(entry) => history.keep(entry),
+                Err(e) => info!(
+                    "Skipping history line {} in {:?}: {}",
+                    line_number + 1,
+                    path,
+                    e
+                ),
+            }
+        }
+        info!("Loaded {} history entries", history.entries.len());
+        Ok(history)
+    }
+
+    /// Adds an entry and saves it, replacing any entry for the same source
+    /// text and dropping the oldest entries beyond the history size.
+    pub fn push(&mut self, entry: HistoryEntry) -> io::Result<()> {
+        let line = serde_json::to_string(&entry)?;
+        self.keep(entry);
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if self.lines_written + 1 >= 2 * self.max_len.max(1) {
+            return self.save();
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", line)?;
+        self.lines_written += 1;
+        Ok(())
+    }
+
+    /// Rewrites the history file with only the kept entries.
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut contents = String::new();
+        for entry in &self.entries {
+            contents.push_str(&serde_json::to_string(entry)?);
+            contents.push('\n');
+        }
+        // Write a temporary file first so a crash never loses the history
+        let temp_path = path.with_extension("jsonl.tmp");
+        fs::write(&temp_path, contents)?;
+        fs::rename(&temp_path, path)?;
+        self.lines_written = self.entries.len();
+        Ok(())
+    }
+
+    /// Changes the history size, dropping the oldest entries if it shrinks.
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len;
+        self.truncate();
+    }
+
+    /// The entry at `index`.
+    pub fn get(&self, index: usize) -> Option<&HistoryEntry> {
+        self.entries.get(index)
+    }
+
+    /// The unpinned entries with their indices, newest first.
+    pub fn recent(&self) -> impl Iterator<Item = (usize, &HistoryEntry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, entry)| !entry.pinned)
+    }
+
+    /// The pinned entries with their indices, in the order they were pinned.
+    pub fn pinned(&self) -> impl Iterator<Item = (usize, &HistoryEntry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.pinned)
+    }
+
+    /// Pins the entry at `index` after the other pinned entries. Returns
+    /// false if there is no such entry.
+    pub fn pin(&mut self, index: usize) -> io::Result<bool> {
+        let Some(mut entry) = self.entries.remove(index) else {
+            return Ok(false);
+        };
+        entry.pinned = true;
+        self.entries.push_back(entry);
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Unpins the entry at `index`, making it the most recent entry. Returns
+    /// false if there is no such entry.
+    pub fn unpin(&mut self, index: usize) -> io::Result<bool> {
+        let Some(mut entry) = self.entries.remove(index) else {
+            return Ok(false);
+        };
+        entry.pinned = false;
+        self.keep(entry);
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Removes every unpinned entry.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.entries.retain(|entry| entry.pinned);
+        self.save()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn keep(&mut self, mut entry: HistoryEntry) {
+        if let Some(index) = self.entries.iter().position(|e| e.source == entry.source) {
+            let existing = self.entries.remove(index).unwrap();
+            if existing.pinned {
+                // A pinned text stays where it is, with the latest translations
+                entry.pinned = true;
+                self.entries.insert(index, entry);
+                return;
+            }
+        }
+        self.entries.push_back(entry);
+        self.truncate();
+    }
+
+    fn truncate(&mut self) {
+        let mut unpinned = self.entries.iter().filter(|e| !e.pinned).count();
+        while unpinned > self.max_len {
+            let oldest = self.entries.iter().position(|e| !e.pinned).unwrap();
+            self.entries.remove(oldest);
+            unpinned -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_history(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "gofer-history-{}-{}.jsonl",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn entry(source: &str, timestamp: u64) -> HistoryEntry {
+        HistoryEntry {
+            source: source.to_string(),
+            source_name: "English".to_string(),
+            translations: vec![Translation {
+                target_name: "French".to_string(),
+                value: format!("{}-fr", source),
+            }],
+            location: "words.csv:2".to_string(),
+            timestamp,
+            pinned: false,
+        }
+    }
+
+    /// The unpinned sources, newest first.
+    fn sources(history: &History) -> Vec<&str> {
+        history.recent().map(|(_, e)| e.source.as_str()).collect()
+    }
+
+    fn pinned_sources(history: &History) -> Vec<&str> {
+        history.pinned().map(|(_, e)| e.source.as_str()).collect()
+    }
+
+    fn index_of(history: &History, source: &str) -> usize {
+        history
+            .recent()
+            .chain(history.pinned())
+            .find(|(_, e)| e.source == source)
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn keeps_the_most_recent_entries() {
+        let mut history = History::in_memory(2);
+        for (i, source) in ["a", "b", "c"].into_iter().enumerate() {
+            history.push(entry(source, i as u64)).unwrap();
+        }
+        assert_eq!(sources(&history), vec!["c", "b"]);
+    }
+
+    #[test]
+    fn entries_survive_reloading() {
+        let path = temp_history("reload");
+        let mut history = History::load(&path, 3).unwrap();
+        assert!(history.is_empty());
+        for (i, source) in ["a", "b", "c", "d", "e", "f", "g"].into_iter().enumerate() {
+            history.push(entry(source, i as u64)).unwrap();
+        }
+
+        let reloaded = History::load(&path, 3).unwrap();
+        assert_eq!(sources(&reloaded), vec!["g", "f", "e"]);
+        assert_eq!(reloaded.recent().next(), Some((2, &entry("g", 6))));
+
+        // The file is compacted instead of growing forever
+        let lines = fs::read_to_string(&path).unwrap().lines().count();
+        assert!(lines < 6, "{} lines", lines);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn unreadable_lines_are_skipped() {
+        let path = temp_history("corrupt");
+        let good = serde_json::to_string(&entry("a", 1)).unwrap();
+        fs::write(&path, format!("{}\nnot json\n", good)).unwrap();
+
+        let history = History::load(&path, 10).unwrap();
+        assert_eq!(sources(&history), vec!["a"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn repeated_lookups_move_to_the_top() {
+        let mut history = History::in_memory(3);
+        history.push(entry("a", 1)).unwrap();
+        history.push(entry("b", 2)).unwrap();
+        history.push(entry("a", 3)).unwrap();
+        assert_eq!(sources(&history), vec!["a", "b"]);
+        assert_eq!(history.recent().next().unwrap().1.timestamp, 3);
+    }
+
+    #[test]
+    fn pinned_entries_are_never_evicted() {
+        let mut history = History::in_memory(2);
+        history.push(entry("a", 1)).unwrap();
+        assert!(history.pin(index_of(&history, "a")).unwrap());
+        for (i, source) in ["b", "c", "d"].into_iter().enumerate() {
+            history.push(entry(source, i as u64 + 2)).unwrap();
+        }
+        assert_eq!(pinned_sources(&history), vec!["a"]);
+        assert_eq!(sources(&history), vec!["d", "c"]);
+
+        // Looking a pinned text up again keeps it pinned
+        history.push(entry("a", 9)).unwrap();
+        assert_eq!(pinned_sources(&history), vec!["a"]);
+        assert_eq!(sources(&history), vec!["d", "c"]);
+
+        assert!(history.unpin(index_of(&history, "a")).unwrap());
+        assert!(pinned_sources(&history).is_empty());
+        assert_eq!(sources(&history), vec!["a", "d"]);
+        assert!(!history.pin(10).unwrap());
+    }
+
+    #[test]
+    fn clear_keeps_pinned_entries() {
+        let path = temp_history("clear");
+        let mut history = History::load(&path, 5).unwrap();
+        history.push(entry("a", 1)).unwrap();
+        history.push(entry("b", 2)).unwrap();
+        history.pin(index_of(&history, "b")).unwrap();
+        history.push(entry("c", 3)).unwrap();
+        history.clear().unwrap();
+        assert!(sources(&history).is_empty());
+
+        let reloaded = History::load(&path, 5).unwrap();
+        assert_eq!(pinned_sources(&reloaded), vec!["b"]);
+        assert!(sources(&reloaded).is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+}
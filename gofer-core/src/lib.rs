@@ -0,0 +1,22 @@
+//! Platform-independent core of Gofer2.
+//!
+//! Loads CSV mappings, looks up translations, ranks fuzzy search results and
+//! detects the double-copy gesture. Frontends reach the system clipboard
+//! through [`clipboard::ClipboardBackend`] and show results through
+//! [`notifier::Notifier`]. Nothing in here depends on AppKit, so the lookup
+//! engine can be embedded in other tools and tested on any platform.
+
+pub mod clipboard;
+pub mod config;
+pub mod data;
+pub mod history;
+pub mod lint;
+pub mod logging;
+pub mod misses;
+pub mod normalize;
+pub mod notifier;
+pub mod search;
+#[cfg(test)]
+mod test_support;
+pub mod trigger;
+pub mod watch;
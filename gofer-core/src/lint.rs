@@ -0,0 +1,466 @@
+// gofer-core/src/lint.rs
+use crate::config::MappingsConfig;
+use crate::data::{self, Mapping, MappingDiagnostic, MappingOrigin, MappingStore};
+use csv::ReaderBuilder;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// How serious a [`Finding`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Probably intended, but worth a look.
+    Warning,
+    /// Data that the app cannot load or will silently drop.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found by [`lint`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub diagnostic: MappingDiagnostic,
+}
+
+impl Finding {
+    fn new(
+        severity: Severity,
+        path: &Path,
+        line: Option<u64>,
+        column: Option<usize>,
+        reason: String,
+    ) -> Self {
+        Finding {
+            severity,
+            diagnostic: MappingDiagnostic::new(path, line, column, reason),
+        }
+    }
+}
+
+/// Formats like a compiler message, e.g. "words.csv:3:2: error: ...".
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let diagnostic = &self.diagnostic;
+        write!(f, "{}", diagnostic.path.display())?;
+        if let Some(line) = diagnostic.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = diagnostic.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        write!(f, ": {}: {}", self.severity, diagnostic.reason)
+    }
+}
+
+/// Checks the mapping files in `user_dir` and `app_dir` the way the app
+/// loads them, sorted by file and line.
+///
+/// Errors are problems the loader reports or silently drops data for:
+/// unparseable rows and rows without a source or translations. Warnings
+/// cover empty translations, whitespace the loader trims, header names
+/// spelled differently across files, source texts defined more than once,
+/// which are all kept as lookup candidates, and user mappings that
+/// translate a bundled source text differently.
+pub fn lint(app_dir: &Path, user_dir: Option<&Path>, config: &MappingsConfig) -> Vec<Finding> {
+    let (store, diagnostics) = MappingStore::load(app_dir, user_dir, config);
+    let mut findings: Vec<Finding> = diagnostics
+        .into_iter()
+        .map(|diagnostic| Finding {
+            severity: Severity::Error,
+            diagnostic,
+        })
+        .collect();
+
+    // Directory problems were already reported by the loader
+    let mut ignored = Vec::new();
+    let paths: Vec<PathBuf> = user_dir
+        .into_iter()
+        .chain(std::iter::once(app_dir))
+        .flat_map(|dir| data::mapping_files(dir, config, &mut ignored))
+        .collect();
+
+    let mut headers = Vec::new();
+    for path in &paths {
+        if let Some(header) = lint_file_cells(path, &mut findings) {
+            headers.push((path.as_path(), header));
+        }
+    }
+    lint_headers(&headers, &mut findings);
+    lint_duplicates(store.get_all_mappings(), config, &mut findings);
+
+    findings.sort_by(|a, b| {
+        let a = &a.diagnostic;
+        let b = &b.diagnostic;
+        (&a.path, a.line, a.column).cmp(&(&b.path, b.line, b.column))
+    });
+    findings
+}
+
+/// Checks each cell of a file for whitespace and missing values, and
+/// returns its header. Parse errors are left to the loader.
+fn lint_file_cells(path: &Path, findings: &mut Vec<Finding>) -> Option<Vec<String>> {
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .has_headers(false)
+        .from_path(path)
+        .ok()?;
+    let mut records = reader.records();
+    let header = records.next()?.ok()?;
+    if header.len() < 2 {
+        return None;
+    }
+    let header: Vec<String> = header.iter().map(str::to_string).collect();
+    check_whitespace(path, 1, header.iter().map(String::as_str), findings);
+
+    for record in records.flatten() {
+        let line = record.position().map_or(0, |pos| pos.line());
+        if record.len() < 2 {
+            continue;
+        }
+        check_whitespace(path, line, record.iter(), findings);
+
+        let source = record[0].trim();
+        if source.is_empty() {
+            findings.push(Finding::new(
+                Severity::Error,
+                path,
+                Some(line),
+                Some(1),
+                "Empty source text; the row is skipped".to_string(),
+            ));
+            continue;
+        }
+
+        // Missing trailing cells count as empty, like in the loader
+        let empty_columns: Vec<usize> = (1..header.len())
+            .filter(|&i| record.get(i).is_none_or(|value| value.trim().is_empty()))
+            .collect();
+        if empty_columns.len() == header.len() - 1 {
+            findings.push(Finding::new(
+                Severity::Error,
+                path,
+                Some(line),
+                None,
+                format!("No translations for {:?}; the row is skipped", source),
+            ));
+            continue;
+        }
+        for i in empty_columns {
+            findings.push(Finding::new(
+                Severity::Warning,
+                path,
+                Some(line),
+                Some(i + 1),
+                format!("Empty {} translation for {:?}", header[i].trim(), source),
+            ));
+        }
+    }
+    Some(header)
+}
+
+fn check_whitespace<'a>(
+    path: &Path,
+    line: u64,
+    cells: impl Iterator<Item = &'a str>,
+    findings: &mut Vec<Finding>,
+) {
+    for (i, cell) in cells.enumerate() {
+        if !cell.trim().is_empty() && cell.trim() != cell {
+            findings.push(Finding::new(
+                Severity::Warning,
+                path,
+                Some(line),
+                Some(i + 1),
+                format!("Leading or trailing whitespace in {:?} is trimmed", cell),
+            ));
+        }
+    }
+}
+
+/// Flags header names that are spelled differently from the same column in
+/// other files, e.g. "french" next to "French".
+fn lint_headers(headers: &[(&Path, Vec<String>)], findings: &mut Vec<Finding>) {
+    // How often each spelling is used, by case-insensitive name
+    let mut spellings: HashMap<String, HashMap<&str, usize>> = HashMap::new();
+    for (_, header) in headers {
+        for name in header {
+            let name = name.trim();
+            *spellings
+                .entry(name.to_lowercase())
+                .or_default()
+                .entry(name)
+                .or_default() += 1;
+        }
+    }
+
+    for (path, header) in headers {
+        for (i, name) in header.iter().enumerate() {
+            let name = name.trim();
+            let usual = spellings[&name.to_lowercase()]
+                .iter()
+                .max_by_key(|&(spelling, count)| (*count, std::cmp::Reverse(*spelling)))
+                .map(|(spelling, _)| *spelling)
+                .unwrap_or(name);
+            if usual != name {
+                findings.push(Finding::new(
+                    Severity::Warning,
+                    path,
+                    Some(1),
+                    Some(i + 1),
+                    format!(
+                        "Header {:?} is spelled {:?} in other files; columns are matched by exact name",
+                        name, usual
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+/// Flags source texts that are defined more than once, comparing them the
+/// way lookups do under each file's key normalization. Every definition is
+/// kept as a lookup candidate, so these are warnings.
+fn lint_duplicates(mappings: &[Mapping], config: &MappingsConfig, findings: &mut Vec<Finding>) {
+    // The first mapping for each key, which is the one lookups return, and
+    // the first for each key within each file
+    let mut first: HashMap<String, &Mapping> = HashMap::new();
+    let mut first_in_file: HashMap<(String, &Path), &Mapping> = HashMap::new();
+    for mapping in mappings {
+        let key = config.normalization(&mapping.path).apply(&mapping.source);
+
+        if let Some(earlier) = first_in_file.get(&(key.clone(), mapping.path.as_path())) {
+            findings.push(Finding::new(
+                Severity::Warning,
+                &mapping.path,
+                Some(mapping.line),
+                Some(1),
+                format!(
+                    "Source text {:?} is also defined on line {}, which takes precedence; both are kept as candidates",
+                    mapping.source, earlier.line
+                ),
+            ));
+            continue;
+        }
+        first_in_file.insert((key.clone(), mapping.path.as_path()), mapping);
+
+        let Some(&primary) = first.get(&key) else {
+            first.insert(key, mapping);
+            continue;
+        };
+        let finding = if let Some(conflict) = bundled_conflict(primary, mapping) {
+            Finding::new(
+                Severity::Warning,
+                &primary.path,
+                Some(primary.line),
+                Some(1),
+                conflict,
+            )
+        } else {
+            Finding::new(
+                Severity::Warning,
+                &mapping.path,
+                Some(mapping.line),
+                Some(1),
+                format!(
+                    "Source text {:?} is also defined in {}, which takes precedence; both are kept as candidates",
+                    mapping.source,
+                    primary.location()
+                ),
+            )
+        };
+        findings.push(finding);
+    }
+}
+
+/// Describes how a user mapping translates a bundled source text
+/// differently, if it does.
+fn bundled_conflict(user: &Mapping, bundled: &Mapping) -> Option<String> {
+    if user.origin != MappingOrigin::User || bundled.origin != MappingOrigin::App {
+        return None;
+    }
+    let differences: Vec<String> = user
+        .translations
+        .iter()
+        .filter_map(|t| {
+            let bundled_value = bundled.target(&t.target_name)?;
+            (bundled_value != t.value).then(|| {
+                format!(
+                    "{} {:?} instead of {:?}",
+                    t.target_name, t.value, bundled_value
+                )
+            })
+        })
+        .collect();
+    if differences.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "{:?} overrides the bundled mapping in {}: {}",
+        user.source,
+        bundled.location(),
+        differences.join(", ")
+    ))
+}
+
+/// Summarizes findings for display, e.g. "2 errors and 1 warning".
+pub fn summarize_findings(findings: &[Finding]) -> String {
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    let warnings = findings.len() - errors;
+    format!(
+        "{} error{} and {} warning{}",
+        errors,
+        if errors == 1 { "" } else { "s" },
+        warnings,
+        if warnings == 1 { "" } else { "s" },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Dirs;
+
+    /// Lints the scratch directory, with paths relative to it.
+    fn findings(dirs: &Dirs) -> Vec<String> {
+        lint(
+            &dirs.app_dir(),
+            Some(&dirs.user_dir()),
+            &MappingsConfig::default(),
+        )
+        .iter()
+        .map(|finding| {
+            let mut finding = finding.clone();
+            finding.diagnostic.path = dirs.relative(&finding.diagnostic.path).to_path_buf();
+            finding.to_string()
+        })
+        .collect()
+    }
+
+    #[test]
+    fn clean_files_have_no_findings() {
+        let dirs = Dirs::new("clean");
+        dirs.write("app", "mappings.csv", "en,fr\nhello,bonjour\n");
+        dirs.write("user", "words.csv", "en,fr\ngoodbye,au revoir\n");
+        assert!(findings(&dirs).is_empty());
+    }
+
+    #[test]
+    fn reports_rows_the_loader_drops() {
+        let dirs = Dirs::new("dropped");
+        dirs.write(
+            "user",
+            "words.csv",
+            "en,fr,de\nhello,,hallo\n,bonjour,hallo\nbye,,\nthanks\n",
+        );
+        assert_eq!(
+            findings(&dirs),
+            vec![
+                "user/words.csv:2:2: warning: Empty fr translation for \"hello\"",
+                "user/words.csv:3:1: error: Empty source text; the row is skipped",
+                "user/words.csv:4: error: No translations for \"bye\"; the row is skipped",
+                "user/words.csv:5:2: error: Row has fewer than 2 columns",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_trimmed_whitespace() {
+        let dirs = Dirs::new("whitespace");
+        dirs.write("user", "words.csv", "en, fr\nhello ,bonjour\n");
+        assert_eq!(
+            findings(&dirs),
+            vec![
+                "user/words.csv:1:2: warning: Leading or trailing whitespace in \" fr\" is trimmed",
+                "user/words.csv:2:1: warning: Leading or trailing whitespace in \"hello \" is trimmed",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_duplicates_within_and_across_files() {
+        let dirs = Dirs::new("duplicates");
+        dirs.write("user", "a.csv", "en,fr\nhello,bonjour\nhello,salut\n");
+        dirs.write("user", "b.csv", "en,fr\nhello,bonjour\n");
+        assert_eq!(
+            findings(&dirs),
+            vec![
+                "user/a.csv:3:1: warning: Source text \"hello\" is also defined on line 2, which takes precedence; both are kept as candidates",
+                "user/b.csv:2:1: warning: Source text \"hello\" is also defined in a.csv:2, which takes precedence; both are kept as candidates",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_duplicates_within_a_later_file() {
+        let dirs = Dirs::new("later-duplicates");
+        dirs.write("user", "a.csv", "en,fr\nhello,bonjour\n");
+        dirs.write(
+            "user",
+            "b.csv",
+            "en,fr\nbye,salut\nhello,allô\nhello,coucou\n",
+        );
+        assert_eq!(
+            findings(&dirs),
+            vec![
+                "user/b.csv:3:1: warning: Source text \"hello\" is also defined in a.csv:2, which takes precedence; both are kept as candidates",
+                "user/b.csv:4:1: warning: Source text \"hello\" is also defined on line 3, which takes precedence; both are kept as candidates",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_conflicts_with_bundled_mappings() {
+        let dirs = Dirs::new("bundled");
+        dirs.write(
+            "app",
+            "mappings.csv",
+            "en,fr\nhello,bonjour\nbye,au revoir\n",
+        );
+        dirs.write("user", "words.csv", "en,fr\nhello,salut\nbye,au revoir\n");
+        assert_eq!(
+            findings(&dirs),
+            vec![
+                "app/mappings.csv:3:1: warning: Source text \"bye\" is also defined in words.csv:3, which takes precedence; both are kept as candidates",
+                "user/words.csv:2:1: warning: \"hello\" overrides the bundled mapping in mappings.csv:2: fr \"salut\" instead of \"bonjour\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_inconsistent_headers() {
+        let dirs = Dirs::new("headers");
+        dirs.write("user", "a.csv", "English,French\nhello,bonjour\n");
+        dirs.write("user", "b.csv", "English,French\nbye,au revoir\n");
+        dirs.write("user", "c.csv", "english,French\nthanks,merci\n");
+        assert_eq!(
+            findings(&dirs),
+            vec![
+                "user/c.csv:1:1: warning: Header \"english\" is spelled \"English\" in other files; columns are matched by exact name",
+            ]
+        );
+    }
+
+    #[test]
+    fn summary_counts_errors_and_warnings() {
+        let finding =
+            |severity| Finding::new(severity, Path::new("a.csv"), None, None, String::new());
+        let findings = [
+            finding(Severity::Error),
+            finding(Severity::Warning),
+            finding(Severity::Warning),
+        ];
+        assert_eq!(summarize_findings(&findings), "1 error and 2 warnings");
+    }
+}
@@ -0,0 +1,355 @@
+// gofer-core/src/logging.rs
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the log file in the state directory.
+pub const LOG_FILE_NAME: &str = "gofer2.log";
+
+/// Where log records go.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogSink {
+    /// The macOS unified log, shown by Console.app.
+    Oslog,
+    /// Standard error.
+    Stderr,
+    /// A file in the state directory, rotated when it grows too large.
+    File,
+}
+
+/// The sinks used when none are configured: the unified log on macOS and
+/// stderr elsewhere. The log file has to be asked for.
+pub fn default_sinks() -> Vec<LogSink> {
+    if cfg!(target_os = "macos") {
+        vec![LogSink::Oslog]
+    } else {
+        vec![LogSink::Stderr]
+    }
+}
+
+/// Which records are logged, written like `RUST_LOG`: a comma-separated
+/// list of a default level and `module=level` directives, such as
+/// `"info,gofer_core::watch=debug"`. A module name on its own logs
+/// everything from that module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogFilter {
+    default: LevelFilter,
+    /// Module directives, longest module first so the most specific wins.
+    directives: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    /// Parses a filter, or explains the first part that is not valid.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut default = LevelFilter::Error;
+        let mut directives = Vec::new();
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match part.split_once('=') {
+                Some((module, level)) => {
+                    let level = level
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("Invalid log level in {:?}", part))?;
+                    directives.push((module.trim().to_string(), level));
+                }
+                None => match part.parse() {
+                    Ok(level) => default = level,
+                    Err(_) => directives.push((part.to_string(), LevelFilter::Trace)),
+                },
+            }
+        }
+        directives.sort_by_key(|(module, _)| Reverse(module.len()));
+        Ok(LogFilter {
+            default,
+            directives,
+        })
+    }
+
+    /// The level for records from `target`, a module path.
+    pub fn level(&self, target: &str) -> LevelFilter {
+        self.directives
+            .iter()
+            .find(|(module, _)| {
+                target
+                    .strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// The most verbose level of any module.
+    pub fn max_level(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+/// A log file that is moved aside once it reaches its maximum size:
+/// `gofer2.log` becomes `gofer2.log.1`, `gofer2.log.1` becomes
+/// `gofer2.log.2`, and so on, keeping at most `max_files` old files.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    /// Opens `path` for appending, creating its directory if needed.
+    pub fn open(path: &Path, max_size: u64, max_files: usize) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            max_size,
+            max_files,
+            file,
+            size,
+        })
+    }
+
+    /// The path of the `n`th old file.
+    fn old_path(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_files > 0 {
+            // The oldest file is overwritten by the one before it
+            for n in (1..self.max_files).rev() {
+                match fs::rename(self.old_path(n), self.old_path(n + 1)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            fs::rename(&self.path, self.old_path(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Formats seconds since the Unix epoch as an ISO 8601 UTC time, e.g.
+/// "2024-03-01T12:30:05Z".
+fn format_timestamp(secs: u64) -> String {
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+    // Civil date from days since 1970-01-01, after Howard Hinnant
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+/// Formats a record as one line of text.
+fn format_record(record: &Record) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    format!(
+        "{} {:<5} {}: {}\n",
+        format_timestamp(now),
+        record.level(),
+        record.target(),
+        record.args()
+    )
+}
+
+/// Writes records to standard error.
+pub struct StderrLog;
+
+impl Log for StderrLog {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let _ = io::stderr().write_all(format_record(record).as_bytes());
+    }
+
+    fn flush(&self) {}
+}
+
+/// Writes records to a rotating file.
+pub struct FileLog {
+    file: Mutex<RotatingFile>,
+}
+
+impl FileLog {
+    pub fn new(file: RotatingFile) -> Self {
+        FileLog {
+            file: Mutex::new(file),
+        }
+    }
+}
+
+impl Log for FileLog {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let mut file = self.file.lock().unwrap();
+        // There is nowhere left to report a failing log file
+        let _ = file.write_all(format_record(record).as_bytes());
+    }
+
+    fn flush(&self) {
+        let _ = self.file.lock().unwrap().flush();
+    }
+}
+
+/// Sends the records that pass a [`LogFilter`] to every sink.
+pub struct Logger {
+    filter: LogFilter,
+    sinks: Vec<Box<dyn Log>>,
+}
+
+impl Logger {
+    pub fn new(filter: LogFilter, sinks: Vec<Box<dyn Log>>) -> Self {
+        Logger { filter, sinks }
+    }
+
+    /// Installs the logger for the rest of the process. Fails if a logger
+    /// has already been installed.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let max_level = self.filter.max_level();
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        for sink in &self.sinks {
+            sink.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        for sink in &self.sinks {
+            sink.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    #[test]
+    fn filter_applies_the_most_specific_module() {
+        let filter = LogFilter::parse("warn, gofer_core=info,gofer_core::watch=trace").unwrap();
+        assert_eq!(filter.level("gofer2::menu"), LevelFilter::Warn);
+        assert_eq!(filter.level("gofer_core::data"), LevelFilter::Info);
+        assert_eq!(filter.level("gofer_core::watch"), LevelFilter::Trace);
+        assert_eq!(filter.level("gofer_core::watch::inner"), LevelFilter::Trace);
+        // Only whole module names match
+        assert_eq!(filter.level("gofer_core_extra"), LevelFilter::Warn);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn filter_accepts_levels_and_bare_modules() {
+        let filter = LogFilter::parse("debug").unwrap();
+        assert_eq!(filter.level("anything"), LevelFilter::Debug);
+
+        let filter = LogFilter::parse("gofer2").unwrap();
+        assert_eq!(filter.level("gofer2::clipboard"), LevelFilter::Trace);
+        assert_eq!(filter.level("notify"), LevelFilter::Error);
+
+        let filter = LogFilter::parse("off").unwrap();
+        assert!(Level::Error > filter.level("gofer2"));
+
+        assert!(LogFilter::parse("gofer2=loud").is_err());
+    }
+
+    #[test]
+    fn file_is_rotated_when_full() {
+        let dir = std::env::temp_dir().join(format!("gofer-log-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join(LOG_FILE_NAME);
+        let old = |n: usize| dir.join(format!("{}.{}", LOG_FILE_NAME, n));
+
+        let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(fs::read_to_string(old(1)).unwrap(), "third\n");
+        assert_eq!(fs::read_to_string(old(2)).unwrap(), "second\n");
+        assert!(!old(3).exists());
+
+        // Reopening continues from the current size
+        let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+        file.write_all(b"fifth\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fifth\n");
+        assert_eq!(fs::read_to_string(old(1)).unwrap(), "fourth\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn timestamps_are_utc_dates() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_709_296_205), "2024-03-01T12:30:05Z");
+    }
+}
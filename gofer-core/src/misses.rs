@@ -0,0 +1,395 @@
+// gofer-core/src/misses.rs
+use crate::config::MissConfig;
+use crate::data::{Mapping, MappingStore};
+use log::info;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the miss log in the user mapping directory. It deliberately does
+/// not end in `.csv`, so it is never loaded as a mapping file.
+pub const MISS_LOG_FILE_NAME: &str = "misses.log";
+
+/// A log of texts that were looked up without finding a mapping.
+///
+/// Each miss is appended as a `timestamp,count,text` CSV row, where the
+/// timestamp is in seconds since the Unix epoch and the count is how often
+/// the text has been missed so far. Once the file has `MAX_ROWS` rows it is
+/// rewritten with one row per text, keeping the most frequent texts.
+#[derive(Debug)]
+pub struct MissLog {
+    path: PathBuf,
+    misses: HashMap<String, Miss>,
+    /// Rows in the file, including superseded and malformed ones.
+    rows: usize,
+    max_rows: usize,
+}
+
+/// Number of rows at which the log file is compacted.
+const MAX_ROWS: usize = 10_000;
+
+#[derive(Clone, Copy, Debug)]
+struct Miss {
+    count: u64,
+    last_seen: u64,
+}
+
+impl MissLog {
+    /// Opens the miss log at `path`, reading the misses recorded so far.
+    /// A missing file gives an empty log.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let (misses, rows) = match fs::File::open(path) {
+            Ok(file) => read_misses(file)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (HashMap::new(), 0),
+            Err(e) => return Err(e),
+        };
+        Ok(MissLog {
+            path: path.to_path_buf(),
+            misses,
+            rows,
+            max_rows: MAX_ROWS,
+        })
+    }
+
+    /// Appends a miss for `text` at `timestamp` and returns how often it
+    /// has been missed.
+    pub fn record(&mut self, text: &str, timestamp: u64) -> io::Result<u64> {
+        let miss = self.misses.entry(text.to_string()).or_insert(Miss {
+            count: 0,
+            last_seen: timestamp,
+        });
+        miss.count += 1;
+        miss.last_seen = timestamp;
+        let count = miss.count;
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut writer = csv::Writer::from_writer(file);
+        writer.write_record([timestamp.to_string(), count.to_string(), text.to_string()])?;
+        writer.flush()?;
+        self.rows += 1;
+        if self.rows >= self.max_rows {
+            self.compact()?;
+        }
+
+        info!(
+            "Logged miss #{} of {} characters",
+            count,
+            text.chars().count()
+        );
+        Ok(count)
+    }
+
+    /// Rewrites the file with one row per text. Only the most frequent half
+    /// of `max_rows` texts is kept, so that appending can go on for a while.
+    fn compact(&mut self) -> io::Result<()> {
+        let keep = self.max_rows / 2;
+        if self.misses.len() > keep {
+            let kept: Vec<String> = self
+                .most_frequent(keep)
+                .into_iter()
+                .map(|(text, _)| text.to_string())
+                .collect();
+            self.misses.retain(|text, _| kept.contains(text));
+        }
+
+        // Oldest first, like appended rows
+        let mut misses: Vec<(&String, &Miss)> = self.misses.iter().collect();
+        misses.sort_by_key(|(text, miss)| (miss.last_seen, *text));
+
+        // Write a new file and move it into place, so a failure keeps the old one
+        let temp = self.path.with_extension("log.tmp");
+        let mut writer = csv::Writer::from_path(&temp)?;
+        for (text, miss) in &misses {
+            writer.write_record([
+                miss.last_seen.to_string(),
+                miss.count.to_string(),
+                text.to_string(),
+            ])?;
+        }
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&temp, &self.path)?;
+
+        self.rows = misses.len();
+        info!("Compacted the miss log to {} texts", self.rows);
+        Ok(())
+    }
+
+    /// The most often missed texts with their counts, most frequent first.
+    /// Ties go to the most recently missed text.
+    pub fn most_frequent(&self, limit: usize) -> Vec<(&str, u64)> {
+        let mut misses: Vec<(&String, &Miss)> = self.misses.iter().collect();
+        misses.sort_by_key(|(text, miss)| (Reverse(miss.count), Reverse(miss.last_seen), *text));
+        misses
+            .into_iter()
+            .take(limit)
+            .map(|(text, miss)| (text.as_str(), miss.count))
+            .collect()
+    }
+
+    /// Writes the most frequent misses as a mapping CSV with empty targets,
+    /// ready for translators to fill in. Returns the number of rows written.
+    pub fn write_skeleton<W: Write>(&self, writer: W, limit: usize) -> io::Result<usize> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(["Source", "Target"])?;
+        let misses = self.most_frequent(limit);
+        for (text, _) in &misses {
+            writer.write_record([*text, ""])?;
+        }
+        writer.flush()?;
+        Ok(misses.len())
+    }
+
+    pub fn len(&self) -> usize {
+        self.misses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.misses.is_empty()
+    }
+}
+
+/// The miss log of a running app, shared by its threads. The file is opened
+/// on first use, so it is only created once something is missed.
+#[derive(Debug)]
+pub struct SharedMissLog {
+    path: Option<PathBuf>,
+    log: Mutex<Option<MissLog>>,
+}
+
+impl SharedMissLog {
+    /// A log at `path`; without one, misses are not logged.
+    pub fn new(path: Option<PathBuf>) -> Self {
+        SharedMissLog {
+            path,
+            log: Mutex::new(None),
+        }
+    }
+
+    /// Runs `f` with the log, opening it first if needed. Returns `None` if
+    /// the log cannot be opened.
+    pub fn with<T>(&self, f: impl FnOnce(&mut MissLog) -> T) -> Option<T> {
+        let mut log = self.log.lock().unwrap();
+        if log.is_none() {
+            let path = self.path.as_ref()?;
+            match MissLog::open(path) {
+                Ok(opened) => *log = Some(opened),
+                Err(e) => {
+                    info!("Failed to open miss log {:?}: {}", path, e);
+                    return None;
+                }
+            }
+        }
+        log.as_mut().map(f)
+    }
+
+    /// Looks `text` up in `store` and returns its candidates in order of
+    /// precedence. When there are none, the miss is logged at `timestamp`
+    /// if `config` asks for it.
+    pub fn look_up<'a>(
+        &self,
+        store: &'a MappingStore,
+        text: &str,
+        config: &MissConfig,
+        timestamp: u64,
+    ) -> Vec<&'a Mapping> {
+        let candidates = store.find_candidates(text);
+        if candidates.is_empty()
+            && config.log
+            && let Some(Err(e)) = self.with(|log| log.record(text, timestamp))
+        {
+            info!("Failed to log miss: {}", e);
+        }
+        candidates
+    }
+}
+
+/// The current time in seconds since the Unix epoch, as used for misses and
+/// history entries.
+pub fn timestamp_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Reads the misses of a log file and counts its rows.
+fn read_misses<R: Read>(reader: R) -> io::Result<(HashMap<String, Miss>, usize)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader);
+    let mut misses: HashMap<String, Miss> = HashMap::new();
+    let mut rows = 0;
+    for result in reader.byte_records() {
+        let record = result?;
+        rows += 1;
+        let line = record.position().map_or(0, |position| position.line());
+        let field = |i| record.get(i).and_then(|s| std::str::from_utf8(s).ok());
+        let (Some(timestamp), Some(text)) =
+            (field(0).and_then(|s| s.parse::<u64>().ok()), field(2))
+        else {
+            info!("Skipping malformed miss log line {}", line);
+            continue;
+        };
+        let stored_count = field(1).and_then(|s| s.parse::<u64>().ok()).unwrap_or(0);
+
+        // Each row counts once, and a compacted row stands for all the misses
+        // before it. A truncated or hand-edited log still adds up.
+        let miss = misses.entry(text.to_string()).or_insert(Miss {
+            count: 0,
+            last_seen: timestamp,
+        });
+        miss.count = (miss.count + 1).max(stored_count);
+        miss.last_seen = miss.last_seen.max(timestamp);
+    }
+    Ok((misses, rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_log(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("gofer-misses-{}-{}.log", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn records_are_counted_and_survive_reopening() {
+        let path = temp_log("reopen");
+        let mut log = MissLog::open(&path).unwrap();
+        assert!(log.is_empty());
+        assert_eq!(log.record("hello", 100).unwrap(), 1);
+        assert_eq!(log.record("world", 101).unwrap(), 1);
+        assert_eq!(log.record("hello", 102).unwrap(), 2);
+
+        let log = MissLog::open(&path).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log.most_frequent(10), vec![("hello", 2), ("world", 1)]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn most_frequent_prefers_recent_misses_on_ties() {
+        let path = temp_log("ties");
+        let mut log = MissLog::open(&path).unwrap();
+        log.record("old", 1).unwrap();
+        log.record("new", 2).unwrap();
+        log.record("common", 3).unwrap();
+        log.record("common", 4).unwrap();
+        assert_eq!(log.most_frequent(2), vec![("common", 2), ("new", 1)]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn skeleton_has_empty_targets() {
+        let path = temp_log("skeleton");
+        let mut log = MissLog::open(&path).unwrap();
+        log.record("hello", 1).unwrap();
+        log.record("a, b", 2).unwrap();
+        log.record("hello", 3).unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(log.write_skeleton(&mut out, 10).unwrap(), 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Source,Target\nhello,\n\"a, b\",\n"
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        let mut log = b"12,1,hello\nnonsense\n13,1,\xff\xfe\n".to_vec();
+        log.extend(b"soon,2,hello\n14,x,hello\n");
+        let (misses, rows) = read_misses(log.as_slice()).unwrap();
+        assert_eq!(rows, 5);
+        assert_eq!(misses.len(), 1);
+        assert_eq!(misses["hello"].count, 2);
+        assert_eq!(misses["hello"].last_seen, 14);
+    }
+
+    #[test]
+    fn stored_counts_are_kept_when_rows_are_missing() {
+        let (misses, _) = read_misses("12,5,hello\n13,6,hello\n14,1,world\n".as_bytes()).unwrap();
+        assert_eq!(misses["hello"].count, 6);
+        assert_eq!(misses["world"].count, 1);
+    }
+
+    #[test]
+    fn full_log_is_compacted_to_the_most_frequent_texts() {
+        let path = temp_log("compact");
+        let mut log = MissLog::open(&path).unwrap();
+        log.max_rows = 4;
+        for (timestamp, text) in ["a", "b", "a", "c", "a", "b"].into_iter().enumerate() {
+            log.record(text, timestamp as u64).unwrap();
+        }
+        // Compacted after "c" and again after the last "b", dropping "c"
+        assert_eq!(fs::read_to_string(&path).unwrap(), "4,3,a\n5,1,b\n");
+
+        // Counting goes on from the compacted rows
+        assert_eq!(log.record("b", 6).unwrap(), 2);
+        let reopened = MissLog::open(&path).unwrap();
+        assert_eq!(reopened.rows, 3);
+        assert_eq!(reopened.most_frequent(10), vec![("a", 3), ("b", 2)]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn only_lookups_without_candidates_are_logged() {
+        use crate::config::MappingsConfig;
+        use crate::data::{MappingOrigin, Translation};
+
+        let store = MappingStore::from_mappings(
+            vec![Mapping {
+                source: "hello".to_string(),
+                source_name: "en".to_string(),
+                translations: vec![Translation {
+                    target_name: "fr".to_string(),
+                    value: "bonjour".to_string(),
+                }],
+                path: PathBuf::from("words.csv"),
+                line: 2,
+                origin: MappingOrigin::User,
+                reversed: false,
+            }],
+            &MappingsConfig::default(),
+        );
+        let path = temp_log("shared");
+        let misses = SharedMissLog::new(Some(path.clone()));
+        let config = MissConfig {
+            log: true,
+            ..MissConfig::default()
+        };
+
+        assert_eq!(misses.look_up(&store, "hello", &config, 1).len(), 1);
+        assert!(!path.exists());
+        assert!(misses.look_up(&store, "world", &config, 2).is_empty());
+        assert!(misses.look_up(&store, "world", &config, 3).is_empty());
+        // Logging is off unless turned on
+        let quiet = MissConfig::default();
+        assert!(misses.look_up(&store, "other", &quiet, 4).is_empty());
+
+        let log = MissLog::open(&path).unwrap();
+        assert_eq!(log.most_frequent(10), vec![("world", 2)]);
+        fs::remove_file(&path).unwrap();
+
+        // Without a path nothing is logged
+        let misses = SharedMissLog::new(None);
+        assert!(misses.look_up(&store, "world", &config, 5).is_empty());
+        assert!(misses.with(|log| log.len()).is_none());
+    }
+}
@@ -0,0 +1,182 @@
+// gofer-core/src/normalize.rs
+use serde::Deserialize;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+/// One step of key normalization, as written in `config.toml`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NormalizeStep {
+    /// Remove leading and trailing whitespace.
+    Trim,
+    /// Compare case-insensitively, using Unicode lowercase mappings.
+    CaseFold,
+    /// Compose characters canonically (Unicode NFC).
+    Nfc,
+    /// Compose characters by compatibility (Unicode NFKC), e.g. "ﬁ" to "fi".
+    Nfkc,
+    /// Replace every run of whitespace with a single space.
+    CollapseWhitespace,
+    /// Remove accents and other combining marks, e.g. "café" to "cafe".
+    StripDiacritics,
+}
+
+/// How source texts are turned into lookup keys.
+///
+/// The same normalization is applied to the source column when a file is
+/// loaded and to the copied text when it is looked up, so e.g. with
+/// case folding "Hello" finds a mapping for "hello". Steps always run in a
+/// fixed order regardless of how they are listed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(from = "Vec<NormalizeStep>")]
+pub struct KeyNormalization {
+    pub trim: bool,
+    pub case_fold: bool,
+    pub nfc: bool,
+    pub nfkc: bool,
+    pub collapse_whitespace: bool,
+    pub strip_diacritics: bool,
+}
+
+impl KeyNormalization {
+    /// Exact matching: keys are used as they are.
+    pub const EXACT: KeyNormalization = KeyNormalization {
+        trim: false,
+        case_fold: false,
+        nfc: false,
+        nfkc: false,
+        collapse_whitespace: false,
+        strip_diacritics: false,
+    };
+
+    /// Turns `text` into a lookup key.
+    pub fn apply(&self, text: &str) -> String {
+        let mut key: String = if self.nfkc {
+            text.nfkc().collect()
+        } else if self.nfc {
+            text.nfc().collect()
+        } else {
+            text.to_string()
+        };
+
+        if self.strip_diacritics {
+            key = key.nfd().filter(|c| !is_combining_mark(*c)).nfc().collect();
+        }
+        if self.case_fold {
+            key = key.to_lowercase();
+        }
+        if self.collapse_whitespace {
+            key = key.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+        if self.trim {
+            key = key.trim().to_string();
+        }
+        key
+    }
+}
+
+/// Trims whitespace, which also covers the trailing newline of a terminal copy.
+impl Default for KeyNormalization {
+    fn default() -> Self {
+        KeyNormalization {
+            trim: true,
+            ..KeyNormalization::EXACT
+        }
+    }
+}
+
+impl From<Vec<NormalizeStep>> for KeyNormalization {
+    fn from(steps: Vec<NormalizeStep>) -> Self {
+        let mut normalization = KeyNormalization::EXACT;
+        for step in steps {
+            match step {
+                NormalizeStep::Trim => normalization.trim = true,
+                NormalizeStep::CaseFold => normalization.case_fold = true,
+                NormalizeStep::Nfc => normalization.nfc = true,
+                NormalizeStep::Nfkc => normalization.nfkc = true,
+                NormalizeStep::CollapseWhitespace => normalization.collapse_whitespace = true,
+                NormalizeStep::StripDiacritics => normalization.strip_diacritics = true,
+            }
+        }
+        normalization
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(steps: &[NormalizeStep], text: &str) -> String {
+        KeyNormalization::from(steps.to_vec()).apply(text)
+    }
+
+    #[test]
+    fn exact_keeps_the_text() {
+        assert_eq!(KeyNormalization::EXACT.apply(" Café\n"), " Café\n");
+        assert_eq!(KeyNormalization::EXACT.apply("cafe\u{301}"), "cafe\u{301}");
+        // Trimming is the default
+        assert_eq!(KeyNormalization::default().apply(" Café\n"), "Café");
+    }
+
+    #[test]
+    fn each_step_normalizes_its_part() {
+        use NormalizeStep::*;
+        assert_eq!(normalize(&[Trim], "\t hello world \n"), "hello world");
+        assert_eq!(normalize(&[CaseFold], "HeLLo ÉCOLE"), "hello école");
+        assert_eq!(normalize(&[Nfc], "cafe\u{301}"), "caf\u{e9}");
+        assert_eq!(normalize(&[Nfc], "\u{fb01}ne"), "\u{fb01}ne");
+        assert_eq!(normalize(&[Nfkc], "\u{fb01}ne \u{2460}"), "fine 1");
+        assert_eq!(normalize(&[Nfkc], "cafe\u{301}"), "caf\u{e9}");
+        assert_eq!(
+            normalize(&[CollapseWhitespace], " good \t\n morning "),
+            "good morning"
+        );
+        assert_eq!(
+            normalize(&[StripDiacritics], "Ångström café"),
+            "Angstrom cafe"
+        );
+        assert_eq!(normalize(&[StripDiacritics], "cafe\u{301}"), "cafe");
+    }
+
+    #[test]
+    fn steps_combine_in_a_fixed_order() {
+        use NormalizeStep::*;
+        let steps = [Trim, CaseFold, StripDiacritics, CollapseWhitespace, Nfkc];
+        assert_eq!(
+            normalize(&steps, "  CAFE\u{301}   \u{fb01}LTRÉ\n"),
+            "cafe filtre"
+        );
+
+        let mut reversed = steps.to_vec();
+        reversed.reverse();
+        assert_eq!(
+            KeyNormalization::from(reversed),
+            KeyNormalization::from(steps.to_vec())
+        );
+
+        // Case folding a decomposed capital still matches the composed one
+        assert_eq!(
+            normalize(&[CaseFold, Nfc], "E\u{301}cole"),
+            normalize(&[CaseFold, Nfc], "\u{c9}cole")
+        );
+    }
+
+    #[test]
+    fn steps_are_read_from_config() {
+        #[derive(Deserialize)]
+        struct File {
+            normalize: KeyNormalization,
+        }
+        let file: File =
+            toml::from_str(r#"normalize = ["strip-diacritics", "case-fold"]"#).unwrap();
+        assert_eq!(
+            file.normalize,
+            KeyNormalization {
+                case_fold: true,
+                strip_diacritics: true,
+                ..KeyNormalization::EXACT
+            }
+        );
+        assert!(toml::from_str::<File>(r#"normalize = ["soundex"]"#).is_err());
+    }
+}
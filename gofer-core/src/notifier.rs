@@ -0,0 +1,300 @@
+// gofer-core/src/notifier.rs
+use log::info;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+/// What a notification button does.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Copy the text to the clipboard.
+    Copy(String),
+    /// Open the search window with the text as the query.
+    Search(String),
+}
+
+/// A button on a notification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Button {
+    /// Identifies the button to the notification server.
+    pub key: &'static str,
+    pub label: &'static str,
+    pub action: Action,
+}
+
+/// The buttons of a lookup notification: copy the first translation, copy
+/// the source text and, where there is a search window, search for it.
+pub fn lookup_buttons(source: &str, translation: &str, search: bool) -> Vec<Button> {
+    let mut buttons = vec![
+        Button {
+            key: "copy-translation",
+            label: "Copy translation",
+            action: Action::Copy(translation.to_string()),
+        },
+        Button {
+            key: "copy-source",
+            label: "Copy source",
+            action: Action::Copy(source.to_string()),
+        },
+    ];
+    if search {
+        buttons.push(Button {
+            key: "open-in-search",
+            label: "Open in search",
+            action: Action::Search(source.to_string()),
+        });
+    }
+    buttons
+}
+
+/// Called with the action of the button the user chose. Notifiers may call
+/// it from any thread.
+pub type ActionHandler = Arc<dyn Fn(Action) + Send + Sync>;
+
+/// Shows lookup results and errors to the user.
+pub trait Notifier: Send {
+    /// Short name for logs, e.g. "D-Bus".
+    fn name(&self) -> &'static str;
+
+    /// Shows a notification with a title and a message.
+    fn notify(&mut self, title: &str, message: &str) -> Result<(), NotifyError>;
+
+    /// Shows a notification with buttons, calling `on_action` if one of them
+    /// is chosen. Notifiers without buttons show just the notification.
+    fn notify_with_buttons(
+        &mut self,
+        title: &str,
+        message: &str,
+        _buttons: &[Button],
+        _on_action: ActionHandler,
+    ) -> Result<(), NotifyError> {
+        self.notify(title, message)
+    }
+}
+
+#[derive(Debug)]
+pub struct NotifyError {
+    /// The [`Notifier::name`] of the failing notifier.
+    pub notifier: &'static str,
+    pub error: String,
+}
+
+impl NotifyError {
+    pub fn new(notifier: &'static str, error: impl fmt::Display) -> Self {
+        NotifyError {
+            notifier,
+            error: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for NotifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} notification error: {}", self.notifier, self.error)
+    }
+}
+
+impl Error for NotifyError {}
+
+/// Prints notifications on stdout, for terminals and headless sessions.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdoutNotifier;
+
+impl Notifier for StdoutNotifier {
+    fn name(&self) -> &'static str {
+        "stdout"
+    }
+
+    fn notify(&mut self, title: &str, message: &str) -> Result<(), NotifyError> {
+        info!("{}: {}", title, message);
+        println!("{}: {}", title, message);
+        Ok(())
+    }
+}
+
+/// Tries each notifier in turn until one succeeds, so a notification still
+/// gets through when the preferred one is unavailable.
+#[derive(Default)]
+pub struct FallbackNotifier {
+    notifiers: Vec<Box<dyn Notifier>>,
+}
+
+impl FallbackNotifier {
+    /// Tries `notifiers` in order of preference.
+    pub fn new(notifiers: Vec<Box<dyn Notifier>>) -> Self {
+        FallbackNotifier { notifiers }
+    }
+
+    /// Adds a notifier to try after the others.
+    pub fn push(&mut self, notifier: Box<dyn Notifier>) {
+        self.notifiers.push(notifier);
+    }
+
+    /// The names of the notifiers in the order they are tried.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.notifiers.iter().map(|n| n.name()).collect()
+    }
+
+    /// Returns the error of the last notifier if none of them succeeds.
+    fn try_each(
+        &mut self,
+        mut notify: impl FnMut(&mut dyn Notifier) -> Result<(), NotifyError>,
+    ) -> Result<(), NotifyError> {
+        let mut last_error = NotifyError::new("fallback", "no notifier available");
+        for notifier in &mut self.notifiers {
+            match notify(notifier.as_mut()) {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    info!("{}; trying the next notifier", e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+}
+
+impl Notifier for FallbackNotifier {
+    fn name(&self) -> &'static str {
+        "fallback"
+    }
+
+    fn notify(&mut self, title: &str, message: &str) -> Result<(), NotifyError> {
+        self.try_each(|notifier| notifier.notify(title, message))
+    }
+
+    fn notify_with_buttons(
+        &mut self,
+        title: &str,
+        message: &str,
+        buttons: &[Button],
+        on_action: ActionHandler,
+    ) -> Result<(), NotifyError> {
+        self.try_each(|notifier| {
+            notifier.notify_with_buttons(title, message, buttons, on_action.clone())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Records what it shows, or always fails.
+    struct FakeNotifier {
+        name: &'static str,
+        works: bool,
+        shown: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Notifier for FakeNotifier {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn notify(&mut self, title: &str, message: &str) -> Result<(), NotifyError> {
+            if !self.works {
+                return Err(NotifyError::new(self.name, "not running"));
+            }
+            let shown = format!("{}: {}: {}", self.name, title, message);
+            self.shown.lock().unwrap().push(shown);
+            Ok(())
+        }
+
+        /// Shows the notification and chooses the first button.
+        fn notify_with_buttons(
+            &mut self,
+            title: &str,
+            message: &str,
+            buttons: &[Button],
+            on_action: ActionHandler,
+        ) -> Result<(), NotifyError> {
+            self.notify(title, message)?;
+            on_action(buttons[0].action.clone());
+            Ok(())
+        }
+    }
+
+    fn fake(name: &'static str, works: bool, shown: &Arc<Mutex<Vec<String>>>) -> Box<dyn Notifier> {
+        Box::new(FakeNotifier {
+            name,
+            works,
+            shown: shown.clone(),
+        })
+    }
+
+    #[test]
+    fn first_working_notifier_is_used() {
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        let mut notifier = FallbackNotifier::new(vec![
+            fake("first", true, &shown),
+            fake("second", true, &shown),
+        ]);
+        notifier.notify("Title", "message").unwrap();
+        assert_eq!(*shown.lock().unwrap(), vec!["first: Title: message"]);
+    }
+
+    #[test]
+    fn failing_notifier_falls_back_to_the_next() {
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        let mut notifier = FallbackNotifier::new(vec![fake("first", false, &shown)]);
+        notifier.push(fake("second", true, &shown));
+        assert_eq!(notifier.names(), vec!["first", "second"]);
+        notifier.notify("Title", "message").unwrap();
+        assert_eq!(*shown.lock().unwrap(), vec!["second: Title: message"]);
+    }
+
+    #[test]
+    fn error_of_the_last_notifier_is_returned() {
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        let mut notifier = FallbackNotifier::new(vec![
+            fake("first", false, &shown),
+            fake("second", false, &shown),
+        ]);
+        let error = notifier.notify("Title", "message").unwrap_err();
+        assert_eq!(error.to_string(), "second notification error: not running");
+        assert!(shown.lock().unwrap().is_empty());
+
+        let error = FallbackNotifier::default().notify("Title", "message");
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn buttons_reach_the_notifier_that_shows_them() {
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        let mut notifier = FallbackNotifier::new(vec![
+            fake("first", false, &shown),
+            fake("second", true, &shown),
+        ]);
+        let chosen = Arc::new(Mutex::new(Vec::new()));
+        let on_action: ActionHandler = {
+            let chosen = chosen.clone();
+            Arc::new(move |action| chosen.lock().unwrap().push(action))
+        };
+        let buttons = lookup_buttons("hello", "bonjour", false);
+        notifier
+            .notify_with_buttons("Title", "message", &buttons, on_action)
+            .unwrap();
+        assert_eq!(*shown.lock().unwrap(), vec!["second: Title: message"]);
+        assert_eq!(
+            *chosen.lock().unwrap(),
+            vec![Action::Copy("bonjour".to_string())]
+        );
+    }
+
+    #[test]
+    fn search_button_is_optional() {
+        let keys = |search| -> Vec<&str> {
+            lookup_buttons("hello", "bonjour", search)
+                .iter()
+                .map(|b| b.key)
+                .collect()
+        };
+        assert_eq!(keys(false), vec!["copy-translation", "copy-source"]);
+        assert_eq!(
+            keys(true),
+            vec!["copy-translation", "copy-source", "open-in-search"]
+        );
+    }
+}
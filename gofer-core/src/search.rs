@@ -0,0 +1,490 @@
+// gofer-core/src/search.rs
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use log::info;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+
+use crate::data::{self, Mapping, MappingStore};
+
+/// Number of results returned when no limit is configured.
+pub const DEFAULT_LIMIT: usize = 10;
+
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub source: String,
+    pub target: String,
+    pub score: i64,
+    /// The CSV file the mapping was read from.
+    pub path: PathBuf,
+    /// 1-based line of the mapping in `path`.
+    pub line: u64,
+}
+
+impl SearchResult {
+    /// Where the mapping was read from, e.g. "glossary.csv:42".
+    pub fn location(&self) -> String {
+        data::format_location(&self.path, self.line)
+    }
+}
+
+/// Number of mappings scored between checks for cancellation.
+const CANCEL_CHECK_INTERVAL: usize = 4096;
+
+/// An inverted index over every searchable text, built once when a store
+/// is loaded.
+///
+/// A fuzzy match is a subsequence match, so a text can only match a query if
+/// it contains every character of the query. The index maps each character to
+/// the texts containing it, and a search only scores the texts found in the
+/// posting lists of all of the query's characters, starting from the rarest.
+/// Longer n-grams would reject valid matches: the characters of a query need
+/// not be adjacent in the texts it matches.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    /// Per mapping, the id of its source text; its translations follow.
+    first_text: Vec<u32>,
+    /// Per text, the position of its mapping.
+    mapping_of: Vec<u32>,
+    /// Per folded character, the ids of the texts containing it, ascending.
+    postings: HashMap<char, Vec<u32>>,
+}
+
+impl SearchIndex {
+    /// Indexes `mappings`, whose positions must match the store's.
+    pub fn build(mappings: &[Mapping]) -> Self {
+        let mut index = SearchIndex::default();
+        for (position, mapping) in mappings.iter().enumerate() {
+            index.first_text.push(index.mapping_of.len() as u32);
+            let texts = std::iter::once(&mapping.source)
+                .chain(mapping.translations.iter().map(|t| &t.value));
+            for text in texts {
+                let id = index.mapping_of.len() as u32;
+                index.mapping_of.push(position as u32);
+                let mut chars: Vec<char> = text.chars().map(fold).collect();
+                chars.sort_unstable();
+                chars.dedup();
+                for c in chars {
+                    index.postings.entry(c).or_default().push(id);
+                }
+            }
+        }
+        index
+    }
+
+    /// Returns the ids of the texts that contain every character of `query`,
+    /// ascending.
+    fn candidates(&self, query: &str) -> Vec<u32> {
+        let mut chars: Vec<char> = query.chars().map(fold).collect();
+        chars.sort_unstable();
+        chars.dedup();
+        let mut lists = Vec::with_capacity(chars.len());
+        for c in chars {
+            match self.postings.get(&c) {
+                Some(list) => lists.push(list),
+                None => return Vec::new(),
+            }
+        }
+
+        // Narrow the shortest list down by the others
+        lists.sort_by_key(|list| list.len());
+        let Some((shortest, others)) = lists.split_first() else {
+            return Vec::new();
+        };
+        let mut candidates = shortest.to_vec();
+        for list in others {
+            candidates.retain(|id| list.binary_search(id).is_ok());
+        }
+        candidates
+    }
+}
+
+/// Folds a character the way the fuzzy matcher compares them, which ignores
+/// ASCII case only.
+fn fold(c: char) -> char {
+    c.to_ascii_lowercase()
+}
+
+/// Ranking key of a hit: higher scores first, then mappings with higher
+/// precedence, then translations in column order.
+type Rank = (i64, Reverse<usize>, Reverse<usize>);
+
+/// Fuzzy-searches every mapping's source and translations for `query` and
+/// returns the best `limit` results, best first.
+///
+/// Each translation of a mapping is one result, scored by the better of its
+/// source and target match, so a row that matches on both sides appears once.
+pub fn search_mappings(store: &MappingStore, query: &str, limit: usize) -> Vec<SearchResult> {
+    search_cancellable(store, query, limit, &|| false).unwrap_or_default()
+}
+
+/// Like [`search_mappings`], but gives up and returns `None` as soon as
+/// `is_cancelled` returns true.
+pub fn search_cancellable(
+    store: &MappingStore,
+    query: &str,
+    limit: usize,
+    is_cancelled: &dyn Fn() -> bool,
+) -> Option<Vec<SearchResult>> {
+    if query.is_empty() || limit == 0 {
+        return Some(Vec::new());
+    }
+
+    let matcher = SkimMatcherV2::default();
+    let mappings = store.get_all_mappings();
+    let index = store.search_index();
+    let candidates = index.candidates(query);
+
+    // Keep the best `limit` hits in a min-heap, so the weakest is evicted first
+    let mut best: BinaryHeap<Reverse<Rank>> = BinaryHeap::with_capacity(limit + 1);
+
+    // Candidates are grouped by mapping, since their ids are ascending
+    let mut rest = candidates.as_slice();
+    let mut checked = 0;
+    while let Some(&first) = rest.first() {
+        if checked % CANCEL_CHECK_INTERVAL == 0 && is_cancelled() {
+            return None;
+        }
+        checked += 1;
+
+        let position = index.mapping_of[first as usize] as usize;
+        let source_id = index.first_text[position];
+        let count = rest
+            .iter()
+            .take_while(|&&id| index.mapping_of[id as usize] as usize == position)
+            .count();
+        let (ids, next) = rest.split_at(count);
+        rest = next;
+
+        let mapping = &mappings[position];
+        let source_score = if ids[0] == source_id {
+            matcher.fuzzy_match(&mapping.source, query)
+        } else {
+            None
+        };
+
+        for (column, translation) in mapping.translations.iter().enumerate() {
+            let target_score = if ids.contains(&(source_id + 1 + column as u32)) {
+                matcher.fuzzy_match(&translation.value, query)
+            } else {
+                None
+            };
+            let Some(score) = source_score.max(target_score) else {
+                continue;
+            };
+
+            best.push(Reverse((score, Reverse(position), Reverse(column))));
+            if best.len() > limit {
+                best.pop();
+            }
+        }
+    }
+
+    let results = best
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((score, Reverse(position), Reverse(column)))| {
+            let mapping = &mappings[position];
+            SearchResult {
+                source: mapping.source.clone(),
+                target: mapping.translations[column].value.clone(),
+                score,
+                path: mapping.path.clone(),
+                line: mapping.line,
+            }
+        })
+        .collect();
+    Some(results)
+}
+
+struct SearchRequest {
+    store: Arc<MappingStore>,
+    query: String,
+    limit: usize,
+    generation: u64,
+}
+
+/// Runs searches on a background thread, so typing in a search field never
+/// waits for the fuzzy matcher.
+///
+/// Every new search cancels the one still running, and only the results of
+/// the latest query are delivered. The thread stops when the worker is dropped.
+pub struct SearchWorker {
+    tx: Option<Sender<SearchRequest>>,
+    generation: Arc<AtomicU64>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SearchWorker {
+    /// Starts the worker. `on_results` is called from the worker thread with
+    /// the query and its results.
+    pub fn spawn<F>(mut on_results: F) -> Self
+    where
+        F: FnMut(&str, Vec<SearchResult>) + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel::<SearchRequest>();
+        let generation = Arc::new(AtomicU64::new(0));
+        let latest = Arc::clone(&generation);
+
+        let thread = thread::spawn(move || {
+            while let Ok(mut request) = rx.recv() {
+                // Skip straight to the newest query
+                while let Ok(newer) = rx.try_recv() {
+                    request = newer;
+                }
+
+                let is_cancelled = || latest.load(Ordering::Relaxed) != request.generation;
+                let results = search_cancellable(
+                    &request.store,
+                    &request.query,
+                    request.limit,
+                    &is_cancelled,
+                );
+                match results {
+                    Some(results) if !is_cancelled() => on_results(&request.query, results),
+                    _ => info!("Search for {:?} cancelled", request.query),
+                }
+            }
+        });
+
+        SearchWorker {
+            tx: Some(tx),
+            generation,
+            thread: Some(thread),
+        }
+    }
+
+    /// Starts searching `store` for `query`, cancelling any search still running.
+    pub fn search(&self, store: Arc<MappingStore>, query: &str, limit: usize) {
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(SearchRequest {
+                store,
+                query: query.to_string(),
+                limit,
+                generation,
+            });
+        }
+    }
+}
+
+impl Drop for SearchWorker {
+    fn drop(&mut self) {
+        // Cancel the running search and close the channel to stop the thread
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        self.tx.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MappingsConfig;
+    use crate::data::{MappingOrigin, Translation};
+
+    fn store(rows: &[(&str, &[&str])]) -> MappingStore {
+        let mappings = rows
+            .iter()
+            .enumerate()
+            .map(|(i, (source, targets))| Mapping {
+                source: source.to_string(),
+                source_name: "en".to_string(),
+                translations: targets
+                    .iter()
+                    .enumerate()
+                    .map(|(column, value)| Translation {
+                        target_name: format!("t{}", column),
+                        value: value.to_string(),
+                    })
+                    .collect(),
+                path: PathBuf::from("test.csv"),
+                line: i as u64 + 2,
+                origin: MappingOrigin::User,
+                reversed: false,
+            })
+            .collect();
+        MappingStore::from_mappings(mappings, &MappingsConfig::default())
+    }
+
+    fn pairs(results: &[SearchResult]) -> Vec<(&str, &str)> {
+        results
+            .iter()
+            .map(|r| (r.source.as_str(), r.target.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn empty_query_or_zero_limit_returns_nothing() {
+        let store = store(&[("hello", &["bonjour"])]);
+        assert!(search_mappings(&store, "", 10).is_empty());
+        assert!(search_mappings(&store, "hello", 0).is_empty());
+    }
+
+    #[test]
+    fn returns_best_matches_not_first_found() {
+        // Many weak scattered matches before the exact one
+        let mut rows: Vec<(String, Vec<&str>)> = (0..50)
+            .map(|i| (format!("h{}e{}l{}l{}o", i, i, i, i), vec!["x"]))
+            .collect();
+        rows.push(("hello".to_string(), vec!["bonjour"]));
+        let rows: Vec<(&str, &[&str])> = rows
+            .iter()
+            .map(|(s, t)| (s.as_str(), t.as_slice()))
+            .collect();
+        let store = store(&rows);
+
+        let results = search_mappings(&store, "hello", 1);
+        assert_eq!(pairs(&results), vec![("hello", "bonjour")]);
+    }
+
+    #[test]
+    fn results_are_sorted_by_score_and_limited() {
+        let store = store(&[
+            ("h_e_l_l_o", &["a"]),
+            ("hello", &["b"]),
+            ("hell_o", &["c"]),
+            ("goodbye", &["d"]),
+        ]);
+
+        let results = search_mappings(&store, "hello", 10);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].source, "hello");
+        assert!(results.windows(2).all(|w| w[0].score >= w[1].score));
+
+        assert_eq!(search_mappings(&store, "hello", 2).len(), 2);
+    }
+
+    #[test]
+    fn source_and_target_hits_are_merged_with_max_score() {
+        // Matches on both the source and the target
+        let store = store(&[("hello", &["hello there"])]);
+        let source_score = SkimMatcherV2::default().fuzzy_match("hello", "hello");
+        let target_score = SkimMatcherV2::default().fuzzy_match("hello there", "hello");
+
+        let results = search_mappings(&store, "hello", 10);
+        assert_eq!(pairs(&results), vec![("hello", "hello there")]);
+        assert_eq!(Some(results[0].score), source_score.max(target_score));
+    }
+
+    #[test]
+    fn target_only_hits_are_found() {
+        let store = store(&[("goodbye", &["au revoir"]), ("hello", &["bonjour"])]);
+        let results = search_mappings(&store, "bonjour", 10);
+        assert_eq!(pairs(&results), vec![("hello", "bonjour")]);
+    }
+
+    #[test]
+    fn each_translation_is_its_own_result() {
+        let store = store(&[("hello", &["bonjour", "hallo"])]);
+        let results = search_mappings(&store, "hello", 10);
+        assert_eq!(
+            pairs(&results),
+            vec![("hello", "bonjour"), ("hello", "hallo")]
+        );
+    }
+
+    #[test]
+    fn ties_keep_precedence_order() {
+        let store = store(&[("hello", &["first"]), ("hello", &["second"])]);
+        let results = search_mappings(&store, "hello", 10);
+        assert_eq!(
+            pairs(&results),
+            vec![("hello", "first"), ("hello", "second")]
+        );
+        assert_eq!(results[0].line, 2);
+    }
+
+    #[test]
+    fn index_never_drops_a_fuzzy_match() {
+        let rows: &[(&str, &[&str])] = &[
+            ("Hello World", &["Bonjour le monde", "Hallo Welt"]),
+            ("café", &["Kaffee", "CAFÉ"]),
+            ("Straße", &["street"]),
+            ("東京", &["Tokyo"]),
+            ("naïve", &[""]),
+            ("x", &["y"]),
+        ];
+        let store = store(rows);
+        let matcher = SkimMatcherV2::default();
+        let queries = [
+            "hw", "HW", "hlo", "wlt", "café", "CAFÉ", "Café", "cfé", "strasse", "aße", "東",
+            "東京", "ky", "ïv", "NAÏVE", "é", "o", "zz", "x y",
+        ];
+
+        for query in queries {
+            let mut expected: Vec<(&str, &str)> = Vec::new();
+            for (source, targets) in rows {
+                for target in *targets {
+                    if matcher.fuzzy_match(source, query).is_some()
+                        || matcher.fuzzy_match(target, query).is_some()
+                    {
+                        expected.push((source, target));
+                    }
+                }
+            }
+            let results = search_mappings(&store, query, 100);
+            let mut found = pairs(&results);
+            found.sort();
+            expected.sort();
+            assert_eq!(found, expected, "query {:?}", query);
+        }
+    }
+
+    #[test]
+    fn index_only_yields_texts_with_every_character() {
+        let store = store(&[("hello", &["bonjour"]), ("help", &["aide"])]);
+        let index = store.search_index();
+        // Ids: hello 0, bonjour 1, help 2, aide 3
+        assert_eq!(index.candidates("HEL"), vec![0, 2]);
+        assert_eq!(index.candidates("lo"), vec![0]);
+        assert_eq!(index.candidates("oj"), vec![1]);
+        assert!(index.candidates("hq").is_empty());
+    }
+
+    #[test]
+    fn cancelled_search_returns_nothing() {
+        let store = store(&[("hello", &["bonjour"])]);
+        assert!(search_cancellable(&store, "hello", 10, &|| true).is_none());
+        assert!(search_cancellable(&store, "hello", 10, &|| false).is_some());
+    }
+
+    #[test]
+    fn worker_skips_superseded_queries() {
+        let store = Arc::new(store(&[("hello", &["bonjour"]), ("help", &["aide"])]));
+        let (results_tx, results_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let worker = SearchWorker::spawn(move |query, results| {
+            results_tx.send((query.to_string(), results.len())).unwrap();
+            // Hold the worker on its first search while newer ones arrive
+            if query == "h" {
+                release_rx.recv().unwrap();
+            }
+        });
+
+        let timeout = std::time::Duration::from_secs(5);
+        worker.search(Arc::clone(&store), "h", 10);
+        assert_eq!(
+            results_rx.recv_timeout(timeout).unwrap(),
+            ("h".to_string(), 2)
+        );
+        worker.search(Arc::clone(&store), "he", 10);
+        worker.search(Arc::clone(&store), "hel", 10);
+        worker.search(Arc::clone(&store), "help", 10);
+        release_tx.send(()).unwrap();
+
+        assert_eq!(
+            results_rx.recv_timeout(timeout).unwrap(),
+            ("help".to_string(), 1)
+        );
+        drop(worker);
+        assert!(results_rx.recv().is_err());
+    }
+}
@@ -0,0 +1,50 @@
+// gofer-core/src/test_support.rs
+//! Fixtures shared by the unit tests.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A scratch directory with `app` and `user` mapping directories, removed
+/// when dropped.
+pub struct Dirs {
+    pub root: PathBuf,
+}
+
+impl Dirs {
+    /// Creates an empty scratch directory; `name` must be unique across the
+    /// tests, since they run in parallel.
+    pub fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("gofer-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("app")).unwrap();
+        fs::create_dir_all(root.join("user")).unwrap();
+        Dirs { root }
+    }
+
+    pub fn app_dir(&self) -> PathBuf {
+        self.root.join("app")
+    }
+
+    pub fn user_dir(&self) -> PathBuf {
+        self.root.join("user")
+    }
+
+    pub fn path(&self, dir: &str, name: &str) -> PathBuf {
+        self.root.join(dir).join(name)
+    }
+
+    pub fn write(&self, dir: &str, name: &str, contents: impl AsRef<[u8]>) {
+        fs::write(self.path(dir, name), contents).unwrap();
+    }
+
+    /// `path` relative to the scratch directory, for stable assertions.
+    pub fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap()
+    }
+}
+
+impl Drop for Dirs {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
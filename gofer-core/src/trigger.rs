@@ -0,0 +1,875 @@
+// gofer-core/src/trigger.rs
+use log::info;
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How close together two copies of the same text must be by default to
+/// count as one gesture.
+pub const DOUBLE_COPY_WINDOW: Duration = Duration::from_secs(1);
+
+/// How long a selection must stay unchanged by default before it is looked
+/// up.
+pub const SELECTION_DWELL: Duration = Duration::from_millis(600);
+
+/// What to do when a copy gesture is detected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TriggerAction {
+    /// Ignore the gesture.
+    None,
+    /// Show the translation in a notification and the menu.
+    Notify,
+    /// Like `Notify`, and also replace the clipboard with the translation.
+    Replace,
+}
+
+/// What starts a lookup.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TriggerSource {
+    /// Copy gestures on the clipboard.
+    #[default]
+    Clipboard,
+    /// Text left selected for the dwell time. Only X11 and Wayland have a
+    /// PRIMARY selection to watch.
+    Selection,
+}
+
+/// The timing and length of the copy gestures the detector looks for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Gesture {
+    /// Maximum time between two copies of the same sequence.
+    pub window: Duration,
+    /// The longest sequence with an action. Reaching it triggers at once;
+    /// shorter sequences of two or more trigger once the window has passed
+    /// without another copy.
+    pub max_copies: u32,
+}
+
+impl Default for Gesture {
+    fn default() -> Self {
+        Gesture {
+            window: DOUBLE_COPY_WINDOW,
+            max_copies: 2,
+        }
+    }
+}
+
+/// A source of the current time, so the detector can be driven by a fake
+/// clock in tests.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// The real monotonic clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a
+/// test can keep one clone and hand another to the detector.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        ManualClock {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// One observation of the clipboard.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClipboardEvent {
+    /// A counter that changes whenever the clipboard contents change, like
+    /// `NSPasteboard.changeCount`.
+    pub change_count: i64,
+    /// The clipboard text, or `None` if it holds no text.
+    pub text: Option<String>,
+    pub at: Instant,
+}
+
+/// What the detector reports for an event.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TriggerOutput {
+    /// The same text was copied `copies` times in a row, quickly enough.
+    Triggered { text: String, copies: u32 },
+    /// The text stayed selected for the dwell time.
+    Selected { text: String },
+}
+
+/// Detects repeated-copy gestures from successive clipboard observations.
+///
+/// A pure state machine: it never touches the clipboard and never reads the
+/// time itself except through its [`Clock`]. The rules are:
+///
+/// - The first event only records the clipboard as it was when monitoring
+///   started; it is not a copy.
+/// - An event with an unchanged change count is not a copy, but still
+///   advances time (see below).
+/// - Every other event is a copy. A copy of the same text as the previous
+///   copy, less than the gesture window after it, continues the sequence;
+///   anything else starts a new sequence of one.
+/// - When a sequence reaches [`Gesture::max_copies`] it triggers at once and
+///   starts over. With the default of two, a triple copy triggers once and a
+///   quadruple copy twice.
+/// - A sequence of at least two copies that stops short of the maximum
+///   triggers with its length once the window has passed without another
+///   copy, so callers should keep polling while nothing changes.
+/// - A clipboard without text ends the sequence.
+pub struct TriggerDetector<C: Clock = SystemClock> {
+    clock: C,
+    gesture: Gesture,
+    last_change_count: Option<i64>,
+    last_content: Option<String>,
+    last_copy_time: Option<Instant>,
+    consecutive_copies: u32,
+}
+
+impl TriggerDetector<SystemClock> {
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl Default for TriggerDetector<SystemClock> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Clock> TriggerDetector<C> {
+    pub fn with_clock(clock: C) -> Self {
+        TriggerDetector {
+            clock,
+            gesture: Gesture::default(),
+            last_change_count: None,
+            last_content: None,
+            last_copy_time: None,
+            consecutive_copies: 0,
+        }
+    }
+
+    /// Changes the gesture to look for. A sequence in progress keeps its
+    /// count and is judged by the new gesture.
+    pub fn set_gesture(&mut self, gesture: Gesture) {
+        self.gesture = gesture;
+    }
+
+    /// Whether `change_count` differs from the last one observed, so callers
+    /// can skip reading the clipboard text when nothing changed.
+    pub fn has_changed(&self, change_count: i64) -> bool {
+        self.last_change_count != Some(change_count)
+    }
+
+    /// Records a clipboard change made by the caller itself, such as writing
+    /// a translation back, so it is not mistaken for a copy by the user.
+    pub fn ignore_change(&mut self, change_count: i64) {
+        self.last_change_count = Some(change_count);
+    }
+
+    /// Observes the clipboard now, according to the detector's clock.
+    pub fn poll(&mut self, change_count: i64, text: Option<String>) -> Option<TriggerOutput> {
+        let at = self.clock.now();
+        self.observe(ClipboardEvent {
+            change_count,
+            text,
+            at,
+        })
+    }
+
+    /// Feeds one clipboard observation into the detector.
+    pub fn observe(&mut self, event: ClipboardEvent) -> Option<TriggerOutput> {
+        let previous_count = self.last_change_count.replace(event.change_count);
+        match previous_count {
+            // The clipboard as it was before monitoring started
+            None => return None,
+            Some(count) if count == event.change_count => return self.expire(event.at),
+            Some(_) => {}
+        }
+
+        // A sequence that timed out before this copy is reported first; the
+        // copy itself can then only start a new sequence
+        let expired = self.expire(event.at);
+
+        let Some(text) = event.text else {
+            // Reset if no text content
+            self.consecutive_copies = 0;
+            self.last_content = None;
+            self.last_copy_time = None;
+            return expired;
+        };
+
+        let continues_sequence = self.consecutive_copies > 0
+            && self.last_content.as_deref() == Some(text.as_str())
+            && self
+                .last_copy_time
+                .is_some_and(|last| event.at.saturating_duration_since(last) < self.gesture.window);
+
+        self.consecutive_copies = if continues_sequence {
+            self.consecutive_copies + 1
+        } else {
+            // Different text or too much time has passed
+            1
+        };
+        self.last_content = Some(text.clone());
+        self.last_copy_time = Some(event.at);
+
+        if self.consecutive_copies >= self.gesture.max_copies.max(2) {
+            let copies = self.consecutive_copies;
+            info!(
+                "{} copies detected of {} characters",
+                copies,
+                text.chars().count()
+            );
+            // Start over, so the next copy begins a new sequence
+            self.consecutive_copies = 0;
+            return Some(TriggerOutput::Triggered { text, copies });
+        }
+        expired
+    }
+
+    /// Reports a sequence of two or more copies whose window has passed.
+    fn expire(&mut self, now: Instant) -> Option<TriggerOutput> {
+        let last = self.last_copy_time?;
+        if self.consecutive_copies < 2 || now.saturating_duration_since(last) < self.gesture.window
+        {
+            return None;
+        }
+
+        let copies = std::mem::take(&mut self.consecutive_copies);
+        let text = self.last_content.clone()?;
+        info!(
+            "{} copies detected of {} characters",
+            copies,
+            text.chars().count()
+        );
+        Some(TriggerOutput::Triggered { text, copies })
+    }
+}
+
+/// Detects a selection that has settled, for watching the X11 and Wayland
+/// PRIMARY selection instead of the clipboard.
+///
+/// Selecting text changes the selection many times while the mouse is
+/// dragged, and some applications keep updating the text without reporting
+/// a new change. The rules are:
+///
+/// - The first event only records the selection as it was when monitoring
+///   started.
+/// - A change with non-blank text starts the dwell timer; any further change
+///   of the change count or of the text restarts it. While a selection is
+///   settling, callers must pass the current text on every poll (see
+///   [`needs_text`](SelectionDetector::needs_text)).
+/// - Once the text has stayed the same for the dwell time it triggers,
+///   unless it is the text that triggered last: selecting the same text
+///   again only triggers after something else was selected in between.
+/// - A selection without text, or with blank text, cancels the timer.
+pub struct SelectionDetector<C: Clock = SystemClock> {
+    clock: C,
+    dwell: Duration,
+    last_change_count: Option<i64>,
+    /// The selection waiting out the dwell time, and when it last changed.
+    pending: Option<(String, Instant)>,
+    last_selected: Option<String>,
+}
+
+impl SelectionDetector<SystemClock> {
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl Default for SelectionDetector<SystemClock> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Clock> SelectionDetector<C> {
+    pub fn with_clock(clock: C) -> Self {
+        SelectionDetector {
+            clock,
+            dwell: SELECTION_DWELL,
+            last_change_count: None,
+            pending: None,
+            last_selected: None,
+        }
+    }
+
+    /// Changes how long a selection must stay unchanged.
+    pub fn set_dwell(&mut self, dwell: Duration) {
+        self.dwell = dwell;
+    }
+
+    /// Whether the next poll needs the selection text: the selection has
+    /// changed, or is still settling.
+    pub fn needs_text(&self, change_count: i64) -> bool {
+        self.last_change_count != Some(change_count) || self.pending.is_some()
+    }
+
+    /// Observes the selection now, according to the detector's clock. `text`
+    /// is ignored unless [`needs_text`](SelectionDetector::needs_text) was
+    /// true.
+    pub fn poll(&mut self, change_count: i64, text: Option<String>) -> Option<TriggerOutput> {
+        let at = self.clock.now();
+        self.observe(ClipboardEvent {
+            change_count,
+            text,
+            at,
+        })
+    }
+
+    /// Feeds one selection observation into the detector.
+    pub fn observe(&mut self, event: ClipboardEvent) -> Option<TriggerOutput> {
+        let previous_count = self.last_change_count.replace(event.change_count);
+        let changed = match previous_count {
+            // The selection as it was before monitoring started
+            None => return None,
+            Some(count) => count != event.change_count,
+        };
+        if !changed && self.pending.is_none() {
+            return None;
+        }
+
+        let text = event.text.filter(|text| !text.trim().is_empty());
+        let Some(text) = text else {
+            self.pending = None;
+            self.last_selected = None;
+            return None;
+        };
+        if self
+            .last_selected
+            .as_ref()
+            .is_some_and(|last| *last != text)
+        {
+            self.last_selected = None;
+        }
+
+        match &mut self.pending {
+            Some((pending, since)) if !changed && *pending == text => {
+                if event.at.saturating_duration_since(*since) < self.dwell {
+                    return None;
+                }
+            }
+            // Still being selected: wait for it to settle
+            _ => {
+                self.pending = Some((text, event.at));
+                return None;
+            }
+        }
+
+        let (text, _) = self.pending.take()?;
+        if self.last_selected.as_ref() == Some(&text) {
+            return None;
+        }
+        info!("Selection of {} characters settled", text.chars().count());
+        self.last_selected = Some(text.clone());
+        Some(TriggerOutput::Selected { text })
+    }
+}
+
+/// Remembers the clipboard text a replace action overwrote, so the
+/// replacement can be undone.
+#[derive(Debug, Default)]
+pub struct ReplaceUndo {
+    last: Option<Replacement>,
+}
+
+#[derive(Debug)]
+struct Replacement {
+    original: String,
+    replacement: String,
+}
+
+impl ReplaceUndo {
+    /// Records that `original` on the clipboard was replaced by `replacement`.
+    pub fn record(&mut self, original: &str, replacement: &str) {
+        self.last = Some(Replacement {
+            original: original.to_string(),
+            replacement: replacement.to_string(),
+        });
+    }
+
+    /// Whether there is a replacement to undo.
+    pub fn can_undo(&self) -> bool {
+        self.last.is_some()
+    }
+
+    /// Returns the original text to put back, provided the clipboard still
+    /// holds the replacement; anything copied since then is not overwritten.
+    pub fn undo(&mut self, clipboard: Option<&str>) -> Option<String> {
+        let last = self.last.take()?;
+        if clipboard == Some(last.replacement.as_str()) {
+            Some(last.original)
+        } else {
+            info!("Clipboard changed since the replacement, not undoing");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHORT: Duration = Duration::from_millis(200);
+
+    struct Harness {
+        clock: ManualClock,
+        detector: TriggerDetector<ManualClock>,
+        change_count: i64,
+    }
+
+    impl Harness {
+        /// A detector that has already seen the initial clipboard.
+        fn new() -> Self {
+            let clock = ManualClock::new();
+            let mut detector = TriggerDetector::with_clock(clock.clone());
+            assert_eq!(detector.poll(100, Some("initial".to_string())), None);
+            Harness {
+                clock,
+                detector,
+                change_count: 100,
+            }
+        }
+
+        fn copy(&mut self, text: &str) -> Option<TriggerOutput> {
+            self.change_count += 1;
+            self.detector
+                .poll(self.change_count, Some(text.to_string()))
+        }
+
+        fn copy_non_text(&mut self) -> Option<TriggerOutput> {
+            self.change_count += 1;
+            self.detector.poll(self.change_count, None)
+        }
+
+        fn wait(&self, by: Duration) {
+            self.clock.advance(by);
+        }
+    }
+
+    fn triggered(text: &str) -> Option<TriggerOutput> {
+        triggered_after(text, 2)
+    }
+
+    fn triggered_after(text: &str, copies: u32) -> Option<TriggerOutput> {
+        Some(TriggerOutput::Triggered {
+            text: text.to_string(),
+            copies,
+        })
+    }
+
+    #[test]
+    fn double_copy_triggers() {
+        let mut h = Harness::new();
+        assert_eq!(h.copy("hello"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("hello"), triggered("hello"));
+    }
+
+    #[test]
+    fn single_copy_does_not_trigger() {
+        let mut h = Harness::new();
+        assert_eq!(h.copy("hello"), None);
+        h.wait(Duration::from_secs(5));
+        assert_eq!(h.copy("world"), None);
+    }
+
+    #[test]
+    fn initial_clipboard_is_not_a_copy() {
+        let clock = ManualClock::new();
+        let mut detector = TriggerDetector::with_clock(clock.clone());
+        assert_eq!(detector.poll(7, Some("hello".to_string())), None);
+        clock.advance(SHORT);
+        // Only one real copy so far
+        assert_eq!(detector.poll(8, Some("hello".to_string())), None);
+        clock.advance(SHORT);
+        assert_eq!(
+            detector.poll(9, Some("hello".to_string())),
+            triggered("hello")
+        );
+    }
+
+    #[test]
+    fn unchanged_change_count_is_ignored() {
+        let mut h = Harness::new();
+        assert_eq!(h.copy("hello"), None);
+        // Polling again without a clipboard change must not count as a copy
+        for _ in 0..5 {
+            h.wait(Duration::from_millis(100));
+            assert_eq!(
+                h.detector.poll(h.change_count, Some("hello".to_string())),
+                None
+            );
+        }
+        assert!(!h.detector.has_changed(h.change_count));
+        assert!(h.detector.has_changed(h.change_count + 1));
+    }
+
+    #[test]
+    fn copies_too_far_apart_do_not_trigger() {
+        let mut h = Harness::new();
+        assert_eq!(h.copy("hello"), None);
+        h.wait(DOUBLE_COPY_WINDOW);
+        assert_eq!(h.copy("hello"), None);
+        // The late copy starts a new sequence
+        h.wait(SHORT);
+        assert_eq!(h.copy("hello"), triggered("hello"));
+    }
+
+    #[test]
+    fn window_is_measured_between_consecutive_copies() {
+        let mut h = Harness::new();
+        assert_eq!(h.copy("hello"), None);
+        h.wait(DOUBLE_COPY_WINDOW - Duration::from_millis(1));
+        assert_eq!(h.copy("hello"), triggered("hello"));
+    }
+
+    #[test]
+    fn different_text_restarts_the_sequence() {
+        let mut h = Harness::new();
+        assert_eq!(h.copy("hello"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("world"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("hello"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("hello"), triggered("hello"));
+    }
+
+    #[test]
+    fn triple_copy_triggers_once() {
+        let mut h = Harness::new();
+        assert_eq!(h.copy("hello"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("hello"), triggered("hello"));
+        h.wait(SHORT);
+        assert_eq!(h.copy("hello"), None);
+    }
+
+    #[test]
+    fn quadruple_copy_triggers_twice() {
+        let mut h = Harness::new();
+        let outputs: Vec<_> = (0..4)
+            .map(|_| {
+                h.wait(SHORT);
+                h.copy("hello")
+            })
+            .collect();
+        assert_eq!(
+            outputs,
+            vec![None, triggered("hello"), None, triggered("hello")]
+        );
+    }
+
+    #[test]
+    fn non_text_clipboard_ends_the_sequence() {
+        let mut h = Harness::new();
+        assert_eq!(h.copy("hello"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy_non_text(), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("hello"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("hello"), triggered("hello"));
+    }
+
+    #[test]
+    fn copying_the_initial_text_again_needs_two_copies() {
+        let mut h = Harness::new();
+        assert_eq!(h.copy("initial"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("initial"), triggered("initial"));
+    }
+
+    #[test]
+    fn observe_uses_event_time_not_clock() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let mut detector = TriggerDetector::with_clock(clock);
+        let event = |change_count, at| ClipboardEvent {
+            change_count,
+            text: Some("hello".to_string()),
+            at,
+        };
+        assert_eq!(detector.observe(event(1, start)), None);
+        assert_eq!(detector.observe(event(2, start)), None);
+        assert_eq!(
+            detector.observe(event(3, start + Duration::from_secs(2))),
+            None
+        );
+        assert_eq!(
+            detector.observe(event(4, start + Duration::from_millis(2500))),
+            triggered("hello")
+        );
+    }
+
+    #[test]
+    fn out_of_order_time_does_not_panic() {
+        let clock = ManualClock::new();
+        let start = clock.now() + Duration::from_secs(10);
+        let mut detector = TriggerDetector::with_clock(clock);
+        let event = |change_count, at| ClipboardEvent {
+            change_count,
+            text: Some("hello".to_string()),
+            at,
+        };
+        detector.observe(event(1, start));
+        detector.observe(event(2, start));
+        assert_eq!(
+            detector.observe(event(3, start - Duration::from_secs(1))),
+            triggered("hello")
+        );
+    }
+
+    fn with_gesture(window: Duration, max_copies: u32) -> Harness {
+        let mut h = Harness::new();
+        h.detector.set_gesture(Gesture { window, max_copies });
+        h
+    }
+
+    fn idle(h: &mut Harness) -> Option<TriggerOutput> {
+        h.detector.poll(h.change_count, None)
+    }
+
+    #[test]
+    fn custom_window_is_respected() {
+        let mut h = with_gesture(Duration::from_millis(300), 2);
+        assert_eq!(h.copy("hello"), None);
+        h.wait(Duration::from_millis(400));
+        assert_eq!(h.copy("hello"), None);
+
+        let mut h = with_gesture(Duration::from_secs(3), 2);
+        assert_eq!(h.copy("hello"), None);
+        h.wait(Duration::from_secs(2));
+        assert_eq!(h.copy("hello"), triggered("hello"));
+    }
+
+    #[test]
+    fn triple_copy_triggers_at_once_when_it_is_the_maximum() {
+        let mut h = with_gesture(DOUBLE_COPY_WINDOW, 3);
+        assert_eq!(h.copy("hello"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("hello"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("hello"), triggered_after("hello", 3));
+        // Nothing left pending
+        h.wait(DOUBLE_COPY_WINDOW * 2);
+        assert_eq!(idle(&mut h), None);
+    }
+
+    #[test]
+    fn double_copy_waits_for_window_when_triple_is_possible() {
+        let mut h = with_gesture(DOUBLE_COPY_WINDOW, 3);
+        assert_eq!(h.copy("hello"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("hello"), None);
+
+        h.wait(DOUBLE_COPY_WINDOW - SHORT);
+        assert_eq!(idle(&mut h), None);
+        h.wait(SHORT);
+        assert_eq!(idle(&mut h), triggered("hello"));
+        // Reported only once
+        h.wait(SHORT);
+        assert_eq!(idle(&mut h), None);
+    }
+
+    #[test]
+    fn expired_double_copy_is_reported_by_the_next_copy() {
+        let mut h = with_gesture(DOUBLE_COPY_WINDOW, 3);
+        assert_eq!(h.copy("hello"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("hello"), None);
+        h.wait(DOUBLE_COPY_WINDOW * 2);
+        assert_eq!(h.copy("world"), triggered("hello"));
+        // The new copy started its own sequence
+        h.wait(SHORT);
+        assert_eq!(h.copy("world"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("world"), triggered_after("world", 3));
+    }
+
+    #[test]
+    fn pending_double_copy_is_reported_when_clipboard_loses_text() {
+        let mut h = with_gesture(DOUBLE_COPY_WINDOW, 3);
+        assert_eq!(h.copy("hello"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("hello"), None);
+        h.wait(DOUBLE_COPY_WINDOW);
+        assert_eq!(h.copy_non_text(), triggered("hello"));
+    }
+
+    #[test]
+    fn single_copy_never_expires_into_a_trigger() {
+        let mut h = with_gesture(DOUBLE_COPY_WINDOW, 3);
+        assert_eq!(h.copy("hello"), None);
+        h.wait(DOUBLE_COPY_WINDOW * 2);
+        assert_eq!(idle(&mut h), None);
+    }
+
+    #[test]
+    fn ignored_changes_are_not_copies() {
+        let mut h = Harness::new();
+        assert_eq!(h.copy("hello"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("hello"), triggered("hello"));
+
+        // The translation is written back to the clipboard
+        h.change_count += 1;
+        h.detector.ignore_change(h.change_count);
+        assert!(!h.detector.has_changed(h.change_count));
+        h.wait(SHORT);
+        assert_eq!(
+            h.detector.poll(h.change_count, Some("bonjour".to_string())),
+            None
+        );
+
+        // Copying the translation twice is a fresh gesture
+        h.wait(SHORT);
+        assert_eq!(h.copy("bonjour"), None);
+        h.wait(SHORT);
+        assert_eq!(h.copy("bonjour"), triggered("bonjour"));
+    }
+
+    struct SelectionHarness {
+        clock: ManualClock,
+        detector: SelectionDetector<ManualClock>,
+        change_count: i64,
+    }
+
+    impl SelectionHarness {
+        /// A detector that has already seen the initial selection.
+        fn new() -> Self {
+            let clock = ManualClock::new();
+            let mut detector = SelectionDetector::with_clock(clock.clone());
+            assert_eq!(detector.poll(1, Some("initial".to_string())), None);
+            SelectionHarness {
+                clock,
+                detector,
+                change_count: 1,
+            }
+        }
+
+        fn select(&mut self, text: &str) -> Option<TriggerOutput> {
+            self.change_count += 1;
+            self.detector
+                .poll(self.change_count, Some(text.to_string()))
+        }
+
+        /// Polls without a new change, passing `text` as the current
+        /// selection if the detector asks for it.
+        fn wait(&mut self, by: Duration, text: &str) -> Option<TriggerOutput> {
+            self.clock.advance(by);
+            let text = self
+                .detector
+                .needs_text(self.change_count)
+                .then(|| text.to_string());
+            self.detector.poll(self.change_count, text)
+        }
+    }
+
+    fn selected(text: &str) -> Option<TriggerOutput> {
+        Some(TriggerOutput::Selected {
+            text: text.to_string(),
+        })
+    }
+
+    #[test]
+    fn settled_selection_triggers_once() {
+        let mut h = SelectionHarness::new();
+        assert_eq!(h.select("hello"), None);
+        assert_eq!(h.wait(SELECTION_DWELL - SHORT, "hello"), None);
+        assert_eq!(h.wait(SHORT, "hello"), selected("hello"));
+        assert!(!h.detector.needs_text(h.change_count));
+        assert_eq!(h.wait(SELECTION_DWELL, "hello"), None);
+    }
+
+    #[test]
+    fn dragging_restarts_the_dwell_time() {
+        let mut h = SelectionHarness::new();
+        for text in ["h", "he", "hel", "hell", "hello"] {
+            assert_eq!(h.select(text), None);
+            assert_eq!(h.wait(SHORT, text), None);
+        }
+        assert_eq!(h.wait(SELECTION_DWELL - SHORT * 2, "hello"), None);
+        assert_eq!(h.wait(SHORT, "hello"), selected("hello"));
+    }
+
+    #[test]
+    fn text_changing_without_a_new_change_count_restarts_the_dwell_time() {
+        let mut h = SelectionHarness::new();
+        assert_eq!(h.select("hel"), None);
+        assert_eq!(h.wait(SELECTION_DWELL - SHORT, "hel"), None);
+        assert_eq!(h.wait(SHORT, "hello"), None);
+        assert_eq!(h.wait(SELECTION_DWELL - SHORT, "hello"), None);
+        assert_eq!(h.wait(SHORT, "hello"), selected("hello"));
+    }
+
+    #[test]
+    fn same_selection_again_needs_another_selection_in_between() {
+        let mut h = SelectionHarness::new();
+        h.select("hello");
+        assert_eq!(h.wait(SELECTION_DWELL, "hello"), selected("hello"));
+        // The application claims the selection again with the same text
+        assert_eq!(h.select("hello"), None);
+        assert_eq!(h.wait(SELECTION_DWELL, "hello"), None);
+
+        h.select("world");
+        assert_eq!(h.wait(SELECTION_DWELL, "world"), selected("world"));
+        h.select("hello");
+        assert_eq!(h.wait(SELECTION_DWELL, "hello"), selected("hello"));
+    }
+
+    #[test]
+    fn blank_or_cleared_selection_cancels_the_dwell_time() {
+        let mut h = SelectionHarness::new();
+        assert_eq!(h.select("hello"), None);
+        h.change_count += 1;
+        assert_eq!(h.detector.poll(h.change_count, None), None);
+        assert_eq!(h.wait(SELECTION_DWELL, "hello"), None);
+
+        assert_eq!(h.select("  \n"), None);
+        assert_eq!(h.wait(SELECTION_DWELL, "  \n"), None);
+    }
+
+    #[test]
+    fn undo_restores_original_only_while_replacement_is_on_clipboard() {
+        let mut undo = ReplaceUndo::default();
+        assert!(!undo.can_undo());
+        undo.record("hello", "bonjour");
+        assert!(undo.can_undo());
+        assert_eq!(undo.undo(Some("bonjour")), Some("hello".to_string()));
+        assert!(!undo.can_undo());
+
+        undo.record("hello", "bonjour");
+        assert_eq!(undo.undo(Some("something else")), None);
+        assert!(!undo.can_undo());
+    }
+}
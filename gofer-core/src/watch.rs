@@ -0,0 +1,311 @@
+// gofer-core/src/watch.rs
+use crate::config::CONFIG_FILE_NAME;
+use log::info;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How long the directories must stay quiet before a change is reported.
+/// Editors often emit several events for a single save.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+enum WatchMessage {
+    Fs(notify::Result<Event>),
+    Stop,
+}
+
+/// Watches mapping directories and reports when a CSV file or the config
+/// file is added, edited or removed.
+///
+/// Uses the platform's native file system notifications (inotify on Linux,
+/// FSEvents on macOS). A directory that does not exist yet is picked up as
+/// soon as it is created. Directories are watched by their canonical paths,
+/// since FSEvents reports those (`/private/var` rather than `/var`), and a
+/// symlinked directory reports the paths of its target. The watcher stops
+/// when dropped.
+pub struct MappingWatcher {
+    tx: Sender<WatchMessage>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MappingWatcher {
+    /// Starts watching `dirs` and calls `on_change` from a background thread
+    /// after each burst of relevant changes.
+    pub fn start<F>(dirs: &[PathBuf], mut on_change: F) -> notify::Result<Self>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let fs_tx = tx.clone();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = fs_tx.send(WatchMessage::Fs(event));
+        })?;
+
+        let dirs: Vec<PathBuf> = dirs.iter().map(|dir| canonical(dir)).collect();
+        for dir in &dirs {
+            watch_dir_or_parent(&mut watcher, dir)?;
+        }
+
+        let thread = thread::spawn(move || {
+            // Keep the watcher alive for as long as the thread runs
+            let mut watcher = watcher;
+
+            while let Ok(message) = rx.recv() {
+                let WatchMessage::Fs(event) = message else {
+                    break;
+                };
+                if !is_relevant(&event, &dirs, &mut watcher) {
+                    continue;
+                }
+
+                // Wait for the burst of events to settle
+                loop {
+                    match rx.recv_timeout(DEBOUNCE) {
+                        Ok(WatchMessage::Fs(event)) => {
+                            is_relevant(&event, &dirs, &mut watcher);
+                        }
+                        Ok(WatchMessage::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+                        Err(RecvTimeoutError::Timeout) => break,
+                    }
+                }
+
+                info!("Mapping files changed, reloading");
+                on_change();
+            }
+        });
+
+        Ok(MappingWatcher {
+            tx,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for MappingWatcher {
+    fn drop(&mut self) {
+        let _ = self.tx.send(WatchMessage::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The canonical form of `dir`. A directory that does not exist yet keeps
+/// its name under its canonical parent, so its creation still matches.
+fn canonical(dir: &Path) -> PathBuf {
+    if let Ok(path) = dir.canonicalize() {
+        return path;
+    }
+    match (dir.parent(), dir.file_name()) {
+        (Some(parent), Some(name)) => match parent.canonicalize() {
+            Ok(parent) => parent.join(name),
+            Err(_) => dir.to_path_buf(),
+        },
+        _ => dir.to_path_buf(),
+    }
+}
+
+/// Watches `dir`, or its parent when `dir` does not exist yet so that its
+/// creation is noticed.
+fn watch_dir_or_parent(watcher: &mut RecommendedWatcher, dir: &Path) -> notify::Result<()> {
+    if dir.is_dir() {
+        info!("Watching mappings in: {:?}", dir);
+        return watcher.watch(dir, RecursiveMode::NonRecursive);
+    }
+
+    match dir.parent().filter(|parent| parent.is_dir()) {
+        Some(parent) => {
+            info!("Waiting for {:?} to be created", dir);
+            watcher.watch(parent, RecursiveMode::NonRecursive)
+        }
+        None => {
+            info!("Not watching {:?}: neither it nor its parent exists", dir);
+            Ok(())
+        }
+    }
+}
+
+/// Returns whether `path` is a CSV file or the config file.
+fn is_mapping_file(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()) == Some("csv")
+        || path.file_name().and_then(|s| s.to_str()) == Some(CONFIG_FILE_NAME)
+}
+
+/// Returns whether `event` touches a mapping file in one of `dirs`, or creates or
+/// removes one of `dirs` itself. `dirs` must be canonical. Newly created
+/// directories start being watched.
+fn is_relevant(
+    event: &notify::Result<Event>,
+    dirs: &[PathBuf],
+    watcher: &mut RecommendedWatcher,
+) -> bool {
+    let event = match event {
+        Ok(event) => event,
+        Err(e) => {
+            info!("File watcher error: {}", e);
+            return false;
+        }
+    };
+
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+
+    let mut relevant = false;
+    for path in &event.paths {
+        if dirs.iter().any(|dir| dir == path) {
+            if path.is_dir() {
+                let _ = watcher.watch(path, RecursiveMode::NonRecursive);
+            }
+            relevant = true;
+        } else if is_mapping_file(path)
+            && dirs.iter().any(|dir| {
+                // Only files directly inside, as subdirectories are not loaded
+                path.strip_prefix(dir)
+                    .is_ok_and(|rest| rest.components().count() == 1)
+            })
+        {
+            relevant = true;
+        }
+    }
+    relevant
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, ModifyKind};
+    use std::fs;
+    use std::sync::mpsc::Receiver;
+
+    /// A fresh scratch directory, by its canonical path.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gofer-watch-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    fn event(kind: EventKind, path: PathBuf) -> notify::Result<Event> {
+        Ok(Event::new(kind).add_path(path))
+    }
+
+    /// Starts a watcher that reports each reload on the returned channel.
+    fn watch(dirs: &[PathBuf]) -> (MappingWatcher, Receiver<()>) {
+        let (tx, rx) = mpsc::channel();
+        let watcher = MappingWatcher::start(dirs, move || {
+            let _ = tx.send(());
+        })
+        .unwrap();
+        (watcher, rx)
+    }
+
+    #[test]
+    fn only_mapping_files_in_watched_dirs_are_relevant() {
+        let root = scratch_dir("relevant");
+        let dir = root.join("mappings");
+        let dirs = vec![dir.clone()];
+        let mut watcher = notify::recommended_watcher(|_| {}).unwrap();
+        let modify = EventKind::Modify(ModifyKind::Any);
+        let mut relevant = |event| is_relevant(&event, &dirs, &mut watcher);
+
+        assert!(relevant(event(modify, dir.join("words.csv"))));
+        assert!(relevant(event(modify, dir.join(CONFIG_FILE_NAME))));
+        assert!(!relevant(event(modify, dir.join("notes.txt"))));
+        assert!(!relevant(event(modify, root.join("words.csv"))));
+        assert!(!relevant(event(modify, dir.join("sub").join("words.csv"))));
+        assert!(!relevant(event(
+            EventKind::Access(AccessKind::Any),
+            dir.join("words.csv")
+        )));
+        assert!(!relevant(Err(notify::Error::generic("lost events"))));
+
+        // Creating or removing a watched directory is relevant too
+        assert!(relevant(event(
+            EventKind::Create(CreateKind::Folder),
+            dir.clone()
+        )));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn dirs_are_compared_by_their_canonical_paths() {
+        let root = scratch_dir("canonical");
+        let dir = root.join("mappings");
+        assert_eq!(canonical(&dir), dir);
+        assert_eq!(canonical(&root.join(".").join("mappings")), dir);
+
+        fs::create_dir(&dir).unwrap();
+        #[cfg(unix)]
+        {
+            let link = root.join("link");
+            std::os::unix::fs::symlink(&dir, &link).unwrap();
+            assert_eq!(canonical(&link), dir);
+
+            // Events name the target, not the symlink
+            let dirs = vec![canonical(&link)];
+            let mut watcher = notify::recommended_watcher(|_| {}).unwrap();
+            let modify = EventKind::Modify(ModifyKind::Any);
+            assert!(is_relevant(
+                &event(modify, dir.join("words.csv")),
+                &dirs,
+                &mut watcher
+            ));
+        }
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_dir_is_watched() {
+        let root = scratch_dir("symlink");
+        let dir = root.join("mappings");
+        fs::create_dir(&dir).unwrap();
+        let link = root.join("link");
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+        let (_watcher, reloads) = watch(std::slice::from_ref(&link));
+
+        fs::write(dir.join("words.csv"), "en,fr\nhello,bonjour\n").unwrap();
+        reloads.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn burst_of_changes_is_reported_once() {
+        let dir = scratch_dir("burst");
+        let (_watcher, reloads) = watch(std::slice::from_ref(&dir));
+
+        for name in ["a.csv", "b.csv", "c.csv"] {
+            fs::write(dir.join(name), "en,fr\nhello,bonjour\n").unwrap();
+        }
+        reloads.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(reloads.recv_timeout(DEBOUNCE * 3).is_err());
+
+        // Unrelated files are ignored
+        fs::write(dir.join("notes.txt"), "todo").unwrap();
+        assert!(reloads.recv_timeout(DEBOUNCE * 3).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn directory_created_later_is_watched() {
+        let root = scratch_dir("created");
+        let dir = root.join("mappings");
+        let (_watcher, reloads) = watch(std::slice::from_ref(&dir));
+
+        fs::create_dir(&dir).unwrap();
+        reloads.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        // Files in the new directory are now picked up
+        fs::write(dir.join("words.csv"), "en,fr\nhello,bonjour\n").unwrap();
+        reloads.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
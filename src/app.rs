@@ -0,0 +1,114 @@
+// src/app.rs
+use crate::{CONFIG, STORE};
+use crate::{clipboard, logger, menu, notification, search};
+use cocoa::appkit::{NSApplication, NSApplicationActivationPolicy};
+use cocoa::base::{id, nil};
+use cocoa::foundation::{NSAutoreleasePool, NSString};
+use gofer_core::config::Config;
+use gofer_core::data;
+use gofer_core::trigger::TriggerSource;
+use gofer_core::watch::MappingWatcher;
+use log::info;
+use objc::{class, msg_send, sel, sel_impl};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Loads the mappings into [`STORE`] and reports any problems to the user.
+/// A broken config file keeps the current mappings and settings.
+fn load_mappings(csv_dir: &Path, user_dir: Option<&Path>) {
+    let config = match Config::load_from_dir(user_dir) {
+        Ok(config) => config,
+        Err(e) => {
+            info!("{}; keeping the current mappings", e);
+            notification::show_notification("Config Error", &e.to_string());
+            return;
+        }
+    };
+
+    if config.trigger.source == TriggerSource::Selection {
+        info!("macOS has no selection to watch, using copy gestures");
+    }
+
+    let diagnostics = STORE.reload(csv_dir, user_dir, &config.mappings);
+    *CONFIG.write().unwrap() = config;
+    if diagnostics.is_empty() {
+        info!("Successfully loaded all mappings");
+        return;
+    }
+
+    for diagnostic in &diagnostics {
+        info!("{}", diagnostic);
+    }
+    // Show error notification to user
+    let error_msg = format!(
+        "{}\n{}",
+        data::summarize_diagnostics(&diagnostics),
+        diagnostics[0]
+    );
+    notification::show_notification("Mapping Load Error", &error_msg);
+}
+
+/// Runs the menu bar app until it quits.
+pub fn run() {
+    // Load all CSV mappings from the OSX app resources directory
+    let csv_dir = crate::app_dir();
+    // Load all CSV mappings from the user gofer2 directory
+    let user_dir: Option<PathBuf> = crate::user_dir();
+
+    // Initialize our logger early on.
+    logger::init_logger(user_dir.as_deref());
+    info!("Starting Basic Menu Bar App");
+
+    load_mappings(&csv_dir, user_dir.as_deref());
+
+    // Reload whenever a mapping file is added, edited or removed
+    let watched_dirs: Vec<PathBuf> = std::iter::once(csv_dir.clone())
+        .chain(user_dir.clone())
+        .collect();
+    let _watcher = match MappingWatcher::start(&watched_dirs, move || {
+        load_mappings(&csv_dir, user_dir.as_deref())
+    }) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            info!("Failed to watch mapping directories: {}", e);
+            None
+        }
+    };
+
+    // Register search delegates
+    search::register_search_delegates();
+
+    unsafe {
+        let _pool = NSAutoreleasePool::new(nil);
+        let app = NSApplication::sharedApplication(nil);
+        app.setActivationPolicy_(
+            NSApplicationActivationPolicy::NSApplicationActivationPolicyAccessory,
+        );
+
+        // Register our Objective‑C handler class for menu events.
+        let handler_class = menu::register_selector();
+        let handler: id = msg_send![handler_class, new];
+        // Notification buttons do what the menu items do
+        notification::set_action_handler(Arc::new(menu::perform_action_on_main_thread));
+
+        // Create the status bar item with our custom menu.
+        let _status_item = menu::create_status_item(handler);
+
+        // Optionally, you can also listen for app termination notifications.
+        let notification_center: id = msg_send![class!(NSNotificationCenter), defaultCenter];
+        let quit_notification =
+            NSString::alloc(nil).init_str("NSApplicationWillTerminateNotification");
+        let _: () = msg_send![notification_center,
+            addObserver: handler
+            selector: sel!(applicationWillTerminate:)
+            name: quit_notification
+            object: nil
+        ];
+
+        // Start monitoring the clipboard.
+        clipboard::start_clipboard_monitor();
+
+        // Run the application.
+        app.run();
+    }
+}
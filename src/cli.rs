@@ -0,0 +1,546 @@
+// src/cli.rs
+//
+// Command-line mode, for scripts and terminals. Uses the same loader and
+// user directory as the menu bar app, but nothing from AppKit.
+use gofer_core::config::Config;
+use gofer_core::data::{self, Mapping, MappingStore};
+use gofer_core::lint::{self, Finding, Severity};
+use gofer_core::search::{self, SearchResult};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// The command succeeded.
+pub const EXIT_OK: i32 = 0;
+/// Nothing was found, or validation found problems.
+pub const EXIT_NOT_FOUND: i32 = 1;
+/// The command line or the settings are invalid.
+pub const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+Usage: gofer2 [options] <command>
+
+Commands:
+  lookup <text>               Print the translations of a text
+  search <query> [--limit N]  Fuzzy-search sources and translations
+  list                        Print every mapping
+  validate [--strict]         Check the mapping files for problems;
+                              --strict also fails on warnings
+
+Options:
+  --json                      Print JSON instead of text
+  --app-dir <dir>             Read the bundled mappings from <dir>
+  --user-dir <dir>            Read the user mappings and config.toml from <dir>
+                              instead of ~/.config/gofer
+  -h, --help                  Print this help
+
+Exit status is 0 on success, 1 if nothing was found or validation found
+errors, and 2 for usage or configuration errors.";
+
+#[derive(Debug)]
+enum Command {
+    Lookup(String),
+    Search { query: String, limit: Option<usize> },
+    List,
+    Validate { strict: bool },
+    Help,
+}
+
+#[derive(Debug)]
+struct Options {
+    command: Command,
+    json: bool,
+    app_dir: Option<PathBuf>,
+    user_dir: Option<PathBuf>,
+}
+
+/// Parses the arguments after the program name.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut json = false;
+    let mut app_dir = None;
+    let mut user_dir = None;
+    let mut limit = None;
+    let mut strict = false;
+    let mut positional = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--strict" => strict = true,
+            "-h" | "--help" => positional.insert(0, "help".to_string()),
+            "--app-dir" => {
+                let dir = args.next().ok_or("--app-dir needs a directory")?;
+                app_dir = Some(PathBuf::from(dir));
+            }
+            "--user-dir" => {
+                let dir = args.next().ok_or("--user-dir needs a directory")?;
+                user_dir = Some(PathBuf::from(dir));
+            }
+            "--limit" => {
+                let value = args.next().ok_or("--limit needs a number")?;
+                let value = value
+                    .parse()
+                    .map_err(|_| format!("Invalid limit: {}", value))?;
+                limit = Some(value);
+            }
+            // Everything after "--" is text, even if it looks like an option
+            "--" => positional.extend(args.by_ref().cloned()),
+            option if option.starts_with("--") => {
+                return Err(format!("Unknown option: {}", option));
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let (name, rest) = positional.split_first().ok_or("No command given")?;
+    // Several words are looked up as one text, so quoting is optional
+    let text = rest.join(" ");
+    let command = match name.as_str() {
+        "help" => Command::Help,
+        "lookup" if !text.is_empty() => Command::Lookup(text),
+        "lookup" => return Err("lookup needs a text".to_string()),
+        "search" if !text.is_empty() => Command::Search { query: text, limit },
+        "search" => return Err("search needs a query".to_string()),
+        "list" | "validate" if !rest.is_empty() => {
+            return Err(format!("{} takes no arguments", name));
+        }
+        "list" => Command::List,
+        "validate" => Command::Validate { strict },
+        other => return Err(format!("Unknown command: {}", other)),
+    };
+    if limit.is_some() && !matches!(command, Command::Search { .. }) {
+        return Err("--limit only applies to search".to_string());
+    }
+    if strict && !matches!(command, Command::Validate { .. }) {
+        return Err("--strict only applies to validate".to_string());
+    }
+
+    Ok(Options {
+        command,
+        json,
+        app_dir,
+        user_dir,
+    })
+}
+
+/// Runs the command line and returns the exit status.
+pub fn run(args: &[String]) -> i32 {
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("gofer2: {}\n\n{}", e, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    if let Command::Help = options.command {
+        println!("{}", USAGE);
+        return EXIT_OK;
+    }
+
+    // A directory given on the command line replaces ~/.config/gofer
+    // entirely, so checks in CI do not depend on the machine's own settings
+    let user_dir = match options.user_dir {
+        Some(dir) if !dir.is_dir() => {
+            eprintln!("gofer2: No mapping directory at {:?}", dir);
+            return EXIT_USAGE;
+        }
+        Some(dir) => Some(dir),
+        None => crate::user_dir(),
+    };
+    let config = match Config::load_from_dir(user_dir.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("gofer2: {}", e);
+            return EXIT_USAGE;
+        }
+    };
+    // A directory given on the command line must exist; without bundled
+    // mappings the user's own can still be used
+    let app_dir = match &options.app_dir {
+        Some(dir) if !dir.is_dir() => {
+            eprintln!("gofer2: No mapping directory at {:?}", dir);
+            return EXIT_USAGE;
+        }
+        Some(dir) => dir.clone(),
+        None => {
+            let dir = crate::app_dir();
+            if !dir.is_dir() {
+                eprintln!(
+                    "gofer2: No bundled mappings at {:?}; use --app-dir to read them from elsewhere",
+                    dir
+                );
+            }
+            dir
+        }
+    };
+    if let Command::Validate { strict } = options.command {
+        let findings = lint::lint(&app_dir, user_dir.as_deref(), &config.mappings);
+        return validate(&findings, strict, options.json);
+    }
+    let (store, diagnostics) = MappingStore::load(&app_dir, user_dir.as_deref(), &config.mappings);
+    // The results may be incomplete, so say why
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    if !diagnostics.is_empty() {
+        eprintln!("{}", data::summarize_diagnostics(&diagnostics));
+    }
+
+    match &options.command {
+        Command::Lookup(text) => lookup(&store, text, options.json),
+        Command::Search { query, limit } => {
+            let limit = limit.unwrap_or(config.search.limit);
+            search(&store, query, limit, options.json)
+        }
+        Command::List => list(&store, options.json),
+        Command::Validate { .. } | Command::Help => unreachable!(),
+    }
+}
+
+#[derive(Serialize)]
+struct TranslationJson<'a> {
+    target_name: &'a str,
+    value: &'a str,
+}
+
+#[derive(Serialize)]
+struct MappingJson<'a> {
+    source: &'a str,
+    source_name: &'a str,
+    translations: Vec<TranslationJson<'a>>,
+    location: String,
+    reversed: bool,
+}
+
+impl<'a> From<&'a Mapping> for MappingJson<'a> {
+    fn from(mapping: &'a Mapping) -> Self {
+        MappingJson {
+            source: &mapping.source,
+            source_name: &mapping.source_name,
+            translations: mapping
+                .translations
+                .iter()
+                .map(|t| TranslationJson {
+                    target_name: &t.target_name,
+                    value: &t.value,
+                })
+                .collect(),
+            location: mapping.location(),
+            reversed: mapping.reversed,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SearchResultJson<'a> {
+    source: &'a str,
+    target: &'a str,
+    score: i64,
+    location: String,
+}
+
+impl<'a> From<&'a SearchResult> for SearchResultJson<'a> {
+    fn from(result: &'a SearchResult) -> Self {
+        SearchResultJson {
+            source: &result.source,
+            target: &result.target,
+            score: result.score,
+            location: result.location(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FindingJson<'a> {
+    severity: String,
+    path: String,
+    line: Option<u64>,
+    column: Option<usize>,
+    message: &'a str,
+}
+
+impl<'a> From<&'a Finding> for FindingJson<'a> {
+    fn from(finding: &'a Finding) -> Self {
+        let diagnostic = &finding.diagnostic;
+        FindingJson {
+            severity: finding.severity.to_string(),
+            path: diagnostic.path.display().to_string(),
+            line: diagnostic.line,
+            column: diagnostic.column,
+            message: &diagnostic.reason,
+        }
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("gofer2: Failed to write JSON: {}", e),
+    }
+}
+
+/// Prints a mapping as "source → name: value, name: value (file.csv:3)".
+fn print_mapping(mapping: &Mapping) {
+    let translations: Vec<String> = mapping
+        .translations
+        .iter()
+        .map(|t| format!("{}: {}", t.target_name, t.value))
+        .collect();
+    println!(
+        "{} → {} ({})",
+        mapping.source,
+        translations.join(", "),
+        mapping.location()
+    );
+}
+
+fn lookup(store: &MappingStore, text: &str, json: bool) -> i32 {
+    let candidates = store.find_candidates(text);
+    if json {
+        let candidates: Vec<MappingJson> = candidates.iter().map(|&m| m.into()).collect();
+        print_json(&candidates);
+    } else if candidates.is_empty() {
+        eprintln!("No target text found for: {}", text);
+    } else {
+        for mapping in &candidates {
+            print_mapping(mapping);
+        }
+    }
+
+    if candidates.is_empty() {
+        EXIT_NOT_FOUND
+    } else {
+        EXIT_OK
+    }
+}
+
+fn search(store: &MappingStore, query: &str, limit: usize, json: bool) -> i32 {
+    let results = search::search_mappings(store, query, limit);
+    if json {
+        let results: Vec<SearchResultJson> = results.iter().map(|r| r.into()).collect();
+        print_json(&results);
+    } else {
+        for result in &results {
+            println!(
+                "{}\t{}\t{}",
+                result.source,
+                result.target,
+                result.location()
+            );
+        }
+    }
+
+    if results.is_empty() {
+        EXIT_NOT_FOUND
+    } else {
+        EXIT_OK
+    }
+}
+
+fn list(store: &MappingStore, json: bool) -> i32 {
+    let mappings = store.get_all_mappings();
+    if json {
+        let mappings: Vec<MappingJson> = mappings.iter().map(|m| m.into()).collect();
+        print_json(&mappings);
+    } else {
+        for mapping in mappings {
+            print_mapping(mapping);
+        }
+    }
+    EXIT_OK
+}
+
+fn validate(findings: &[Finding], strict: bool, json: bool) -> i32 {
+    if json {
+        let findings: Vec<FindingJson> = findings.iter().map(|f| f.into()).collect();
+        print_json(&findings);
+    } else {
+        for finding in findings {
+            println!("{}", finding);
+        }
+        if findings.is_empty() {
+            eprintln!("No problems found");
+        } else {
+            eprintln!("{}", lint::summarize_findings(findings));
+        }
+    }
+
+    let failed = findings
+        .iter()
+        .any(|f| strict || f.severity == Severity::Error);
+    if failed { EXIT_NOT_FOUND } else { EXIT_OK }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gofer_core::config::MappingsConfig;
+    use gofer_core::data::{MappingDiagnostic, MappingOrigin, Translation};
+    use std::path::Path;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_args(&args)
+    }
+
+    fn store(sources: &[&str]) -> MappingStore {
+        let mappings = sources
+            .iter()
+            .enumerate()
+            .map(|(i, source)| Mapping {
+                source: source.to_string(),
+                source_name: "en".to_string(),
+                translations: vec![Translation {
+                    target_name: "fr".to_string(),
+                    value: format!("{} (fr)", source),
+                }],
+                path: PathBuf::from("words.csv"),
+                line: i as u64 + 2,
+                origin: MappingOrigin::User,
+                reversed: false,
+            })
+            .collect();
+        MappingStore::from_mappings(mappings, &MappingsConfig::default())
+    }
+
+    fn finding(severity: Severity) -> Finding {
+        Finding {
+            severity,
+            diagnostic: MappingDiagnostic {
+                path: PathBuf::from("words.csv"),
+                line: Some(2),
+                column: None,
+                reason: "problem".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn commands_and_options_are_parsed() {
+        let options = parse(&["--json", "lookup", "good", "morning"]).unwrap();
+        assert!(matches!(options.command, Command::Lookup(ref text) if text == "good morning"));
+        assert!(options.json);
+        assert_eq!(options.app_dir, None);
+        assert_eq!(options.user_dir, None);
+
+        let options = parse(&["search", "helo", "--limit", "5", "--app-dir", "res"]).unwrap();
+        assert!(matches!(
+            options.command,
+            Command::Search { ref query, limit: Some(5) } if query == "helo"
+        ));
+        assert_eq!(options.app_dir.as_deref(), Some(Path::new("res")));
+        assert!(!options.json);
+
+        let options = parse(&["validate", "--strict", "--user-dir", "mappings"]).unwrap();
+        assert!(matches!(
+            options.command,
+            Command::Validate { strict: true }
+        ));
+        assert_eq!(options.user_dir.as_deref(), Some(Path::new("mappings")));
+        assert!(matches!(parse(&["list"]).unwrap().command, Command::List));
+
+        // Text after "--" is never an option
+        let options = parse(&["lookup", "--", "--json"]).unwrap();
+        assert!(matches!(options.command, Command::Lookup(ref text) if text == "--json"));
+        assert!(!options.json);
+    }
+
+    #[test]
+    fn help_wins_over_the_command() {
+        assert!(matches!(parse(&["-h"]).unwrap().command, Command::Help));
+        assert!(matches!(
+            parse(&["lookup", "hello", "--help"]).unwrap().command,
+            Command::Help
+        ));
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        let error = |args: &[&str]| parse(args).unwrap_err();
+        assert_eq!(error(&[]), "No command given");
+        assert_eq!(error(&["translate", "hello"]), "Unknown command: translate");
+        assert_eq!(error(&["--verbose", "list"]), "Unknown option: --verbose");
+        assert_eq!(error(&["list", "--app-dir"]), "--app-dir needs a directory");
+        assert_eq!(
+            error(&["validate", "--user-dir"]),
+            "--user-dir needs a directory"
+        );
+        assert_eq!(
+            error(&["search", "helo", "--limit"]),
+            "--limit needs a number"
+        );
+        assert_eq!(
+            error(&["search", "helo", "--limit", "ten"]),
+            "Invalid limit: ten"
+        );
+        assert_eq!(error(&["lookup"]), "lookup needs a text");
+        assert_eq!(error(&["search"]), "search needs a query");
+        assert_eq!(error(&["list", "extra"]), "list takes no arguments");
+        assert_eq!(error(&["validate", "extra"]), "validate takes no arguments");
+        assert_eq!(
+            error(&["lookup", "hello", "--limit", "3"]),
+            "--limit only applies to search"
+        );
+        assert_eq!(
+            error(&["list", "--strict"]),
+            "--strict only applies to validate"
+        );
+    }
+
+    #[test]
+    fn usage_errors_and_help_have_their_exit_status() {
+        let run = |args: &[&str]| run(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>());
+        assert_eq!(run(&[]), EXIT_USAGE);
+        assert_eq!(run(&["--nope", "list"]), EXIT_USAGE);
+        assert_eq!(run(&["lookup"]), EXIT_USAGE);
+        assert_eq!(run(&["--help"]), EXIT_OK);
+    }
+
+    #[test]
+    fn validate_reads_only_the_given_directories() {
+        let root = std::env::temp_dir().join(format!("gofer-cli-{}", std::process::id()));
+        let (app_dir, user_dir) = (root.join("app"), root.join("user"));
+        std::fs::create_dir_all(&app_dir).unwrap();
+        std::fs::create_dir_all(&user_dir).unwrap();
+        let run = |args: &[&str]| {
+            let mut all = vec!["--app-dir", app_dir.to_str().unwrap()];
+            all.extend(["--user-dir", user_dir.to_str().unwrap()]);
+            all.extend(args);
+            run(&all.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+        };
+
+        std::fs::write(user_dir.join("words.csv"), "en,fr\nhello,bonjour\n").unwrap();
+        assert_eq!(run(&["validate", "--strict"]), EXIT_OK);
+        std::fs::write(user_dir.join("words.csv"), "en,fr\nhello\n").unwrap();
+        assert_eq!(run(&["validate"]), EXIT_NOT_FOUND);
+        // The config.toml next to the mappings is used, not the home one
+        std::fs::write(user_dir.join("config.toml"), "[nope]\n").unwrap();
+        assert_eq!(run(&["validate"]), EXIT_USAGE);
+
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(run(&["validate"]), EXIT_USAGE);
+    }
+
+    #[test]
+    fn lookups_and_searches_fail_when_nothing_is_found() {
+        let store = store(&["hello", "goodbye"]);
+        assert_eq!(lookup(&store, "hello", false), EXIT_OK);
+        assert_eq!(lookup(&store, "hello", true), EXIT_OK);
+        assert_eq!(lookup(&store, "bonsoir", false), EXIT_NOT_FOUND);
+        assert_eq!(lookup(&store, "bonsoir", true), EXIT_NOT_FOUND);
+        assert_eq!(search(&store, "helo", 5, false), EXIT_OK);
+        assert_eq!(search(&store, "xyzzy", 5, true), EXIT_NOT_FOUND);
+        assert_eq!(list(&MappingStore::default(), false), EXIT_OK);
+    }
+
+    #[test]
+    fn validation_fails_on_errors_or_strict_warnings() {
+        assert_eq!(validate(&[], false, false), EXIT_OK);
+        assert_eq!(validate(&[], true, true), EXIT_OK);
+        let warnings = [finding(Severity::Warning)];
+        assert_eq!(validate(&warnings, false, false), EXIT_OK);
+        assert_eq!(validate(&warnings, true, false), EXIT_NOT_FOUND);
+        let errors = [finding(Severity::Warning), finding(Severity::Error)];
+        assert_eq!(validate(&errors, false, true), EXIT_NOT_FOUND);
+    }
+}
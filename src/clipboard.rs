@@ -1,114 +1,223 @@
 // src/clipboard.rs
-use crate::data;
 use crate::menu;
 use crate::notification;
+use crate::{CONFIG, MISSES, STORE};
 use cocoa::appkit::NSPasteboard;
-use cocoa::base::{id, nil};
+use cocoa::base::{BOOL, NO, id, nil};
 use cocoa::foundation::{NSDefaultRunLoopMode, NSString};
+use gofer_core::clipboard::{ClipboardBackend, ClipboardError};
+use gofer_core::history::HistoryEntry;
+use gofer_core::misses::timestamp_now;
+use gofer_core::trigger::{ReplaceUndo, TriggerAction, TriggerDetector, TriggerOutput};
 use log::info;
 use objc::declare::ClassDecl;
 use objc::runtime::{Object, Sel};
 use objc::{class, msg_send, sel, sel_impl};
+use std::fs::File;
+use std::path::Path;
 use std::sync::Mutex;
-use std::time::{Duration, Instant};
-
-// Structure to keep track of clipboard state
-struct ClipboardState {
-    last_change_count: i64,
-    last_content: String,
-    last_copy_time: Instant,
-    consecutive_copies: u32,
-}
 
 // Use a Mutex for thread-safe interior mutability
 lazy_static::lazy_static! {
-    static ref CLIPBOARD_STATE: Mutex<ClipboardState> = Mutex::new(ClipboardState {
-        last_change_count: 0,
-        last_content: String::new(),
-        last_copy_time: Instant::now(),
-        consecutive_copies: 0,
+    static ref DETECTOR: Mutex<TriggerDetector> = Mutex::new(TriggerDetector::new());
+    static ref UNDO: Mutex<ReplaceUndo> = Mutex::new(ReplaceUndo::default());
+}
+
+/// Writes the most frequent misses to `path` as a CSV with empty targets.
+pub fn export_misses(path: &Path) {
+    let limit = CONFIG.read().unwrap().misses.export_limit;
+    let result = MISSES.with(|log| {
+        let file = File::create(path)?;
+        log.write_skeleton(file, limit)
     });
+    match result {
+        Some(Ok(count)) => {
+            info!("Exported {} misses to {:?}", count, path);
+            notification::show_notification(
+                "Misses exported",
+                &format!("{} untranslated texts written to {}", count, path.display()),
+            );
+        }
+        Some(Err(e)) => {
+            info!("Failed to export misses to {:?}: {}", path, e);
+            notification::show_notification("Export Error", &e.to_string());
+        }
+        None => notification::show_notification("Export Error", "The miss log is unavailable"),
+    }
 }
 
-/// Get the current clipboard text content
-unsafe fn get_clipboard_text(pasteboard: id) -> Option<String> {
-    unsafe {
-        let type_str = NSString::alloc(nil).init_str("public.utf8-plain-text");
-        let copied_text: id = msg_send![pasteboard, stringForType: type_str];
-        if copied_text != nil {
-            let c_str = NSString::UTF8String(copied_text);
-            if !c_str.is_null() {
-                return Some(
-                    std::ffi::CStr::from_ptr(c_str)
-                        .to_string_lossy()
-                        .into_owned(),
-                );
-            }
+/// The macOS general pasteboard.
+pub struct CocoaClipboard {
+    pasteboard: id,
+}
+
+impl CocoaClipboard {
+    pub fn general() -> Self {
+        CocoaClipboard {
+            pasteboard: unsafe { NSPasteboard::generalPasteboard(nil) },
         }
-        None
     }
 }
 
-extern "C" fn check_pasteboard(_this: &Object, _cmd: Sel, _timer: id) {
-    unsafe {
-        let mut state = CLIPBOARD_STATE.lock().unwrap();
-        let pasteboard: id = NSPasteboard::generalPasteboard(nil);
-        let current_count: i64 = msg_send![pasteboard, changeCount];
-
-        // If the pasteboard has changed...
-        if current_count != state.last_change_count {
-            state.last_change_count = current_count;
-
-            if let Some(current_text) = get_clipboard_text(pasteboard) {
-                let now = Instant::now();
-                let time_since_last_copy = now.duration_since(state.last_copy_time);
-
-                if current_text == state.last_content
-                    && time_since_last_copy < Duration::from_secs(1)
-                {
-                    state.consecutive_copies += 1;
-
-                    if state.consecutive_copies == 2 {
-                        info!("Double copy detected! Text: {}", current_text);
-
-                        // Look up the target text
-                        if let Some(mapping) = data::find_target(&current_text) {
-                            // Add new menu item with translation
-                            menu::add_menu_item(&current_text, &mapping.value);
-
-                            // Show notification
-                            notification::show_notification(
-                                format!("{} to {}", mapping.source_name, mapping.target_name)
-                                    .as_str(),
-                                format!("{} → {}", current_text, mapping.value).as_str(),
-                            );
-                        } else {
-                            // No mapping found
-                            notification::show_notification(
-                                "No mapping found",
-                                &format!("No target text found for: {}", current_text),
-                            );
-                        }
-
-                        // Reset consecutive copies after processing
-                        state.consecutive_copies = 0;
-                    }
-                } else {
-                    // Reset if it's different text or too much time has passed
-                    state.consecutive_copies = 1;
+impl ClipboardBackend for CocoaClipboard {
+    fn name(&self) -> &'static str {
+        "Cocoa"
+    }
+
+    fn read_text(&mut self) -> Result<Option<String>, ClipboardError> {
+        unsafe {
+            let type_str = NSString::alloc(nil).init_str("public.utf8-plain-text");
+            let copied_text: id = msg_send![self.pasteboard, stringForType: type_str];
+            if copied_text != nil {
+                let c_str = NSString::UTF8String(copied_text);
+                if !c_str.is_null() {
+                    return Ok(Some(
+                        std::ffi::CStr::from_ptr(c_str)
+                            .to_string_lossy()
+                            .into_owned(),
+                    ));
                 }
+            }
+            Ok(None)
+        }
+    }
 
-                state.last_content = current_text;
-                state.last_copy_time = now;
-            } else {
-                // Reset if no text content
-                state.consecutive_copies = 0;
-                state.last_content.clear();
+    fn write_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        unsafe {
+            let _: i64 = msg_send![self.pasteboard, clearContents];
+            let ns_string = NSString::alloc(nil).init_str(text);
+            let written: BOOL = msg_send![self.pasteboard,
+                setString:ns_string
+                forType:NSString::alloc(nil).init_str("public.utf8-plain-text")
+            ];
+            if written == NO {
+                return Err(ClipboardError::new(
+                    self.name(),
+                    "setString:forType: failed",
+                ));
             }
+            Ok(())
+        }
+    }
+
+    fn change_token(&mut self) -> Result<i64, ClipboardError> {
+        unsafe {
+            let change_count: i64 = msg_send![self.pasteboard, changeCount];
+            Ok(change_count)
+        }
+    }
+}
+
+/// Writes text to the clipboard on our own behalf, telling the detector so
+/// the write does not count as a copy. Returns whether it was written.
+fn write_clipboard_text(clipboard: &mut dyn ClipboardBackend, text: &str) -> bool {
+    let result = clipboard
+        .write_text(text)
+        .and_then(|()| clipboard.change_token());
+    match result {
+        Ok(change_token) => {
+            DETECTOR.lock().unwrap().ignore_change(change_token);
+            true
+        }
+        Err(e) => {
+            info!("{}", e);
+            false
         }
     }
 }
 
+/// Copies text chosen in the menu or on a notification. Like our other
+/// writes, it does not count towards a copy gesture.
+pub fn copy_text(text: &str) {
+    if write_clipboard_text(&mut CocoaClipboard::general(), text) {
+        info!(
+            "Copied {} characters to the clipboard",
+            text.chars().count()
+        );
+    }
+}
+
+/// Replaces the clipboard text with its translation, keeping the original
+/// so it can be restored with `undo_replace`.
+fn replace_clipboard_text(original: &str, replacement: &str) {
+    write_clipboard_text(&mut CocoaClipboard::general(), replacement);
+    UNDO.lock().unwrap().record(original, replacement);
+    info!("Replaced the clipboard text with its translation");
+}
+
+/// Whether there is a replacement that `undo_replace` can restore.
+pub fn can_undo_replace() -> bool {
+    UNDO.lock().unwrap().can_undo()
+}
+
+/// Puts the original text back on the clipboard, unless something else has
+/// been copied since it was replaced.
+pub fn undo_replace() {
+    let mut clipboard = CocoaClipboard::general();
+    let current = clipboard.read_text().unwrap_or_else(|e| {
+        info!("{}", e);
+        None
+    });
+    let original = UNDO.lock().unwrap().undo(current.as_deref());
+    if let Some(original) = original {
+        write_clipboard_text(&mut clipboard, &original);
+        info!("Restored the replaced clipboard text");
+    }
+}
+
+/// Looks up the text of a detected gesture and carries out its action.
+fn handle_trigger(current_text: &str, action: TriggerAction) {
+    if action == TriggerAction::None {
+        info!("No action configured for this gesture");
+        return;
+    }
+
+    // Look up the target text
+    let store = STORE.current();
+    let miss_config = CONFIG.read().unwrap().misses.clone();
+    let candidates = MISSES.look_up(&store, current_text, &miss_config, timestamp_now());
+    if let Some(mapping) = candidates.first() {
+        // Add new menu item with translations
+        menu::add_history_entry(HistoryEntry::new(current_text, mapping, timestamp_now()));
+
+        // Show notification
+        notification::show_lookup(current_text, &candidates);
+
+        if action == TriggerAction::Replace {
+            replace_clipboard_text(current_text, &mapping.translations[0].value);
+        }
+    } else {
+        // No mapping found
+        notification::show_miss(current_text, miss_config.notify);
+    }
+}
+
+extern "C" fn check_pasteboard(_this: &Object, _cmd: Sel, _timer: id) {
+    let trigger_config = CONFIG.read().unwrap().trigger.clone();
+    let mut detector = DETECTOR.lock().unwrap();
+    detector.set_gesture(trigger_config.gesture());
+
+    let mut clipboard = CocoaClipboard::general();
+    let Ok(current_count) = clipboard.change_token() else {
+        return;
+    };
+
+    // Only read the text if the pasteboard has changed; polling regardless
+    // lets a pending double copy trigger once its window has passed
+    let text = if detector.has_changed(current_count) {
+        clipboard.read_text().unwrap_or_else(|e| {
+            info!("{}", e);
+            None
+        })
+    } else {
+        None
+    };
+    if let Some(TriggerOutput::Triggered { text, copies }) = detector.poll(current_count, text) {
+        drop(detector);
+        handle_trigger(&text, trigger_config.action(copies));
+    }
+}
+
 pub fn start_clipboard_monitor() {
     unsafe {
         let superclass = class!(NSObject);
@@ -0,0 +1,330 @@
+// src/linux/dbus.rs
+//
+// Desktop notifications through the freedesktop.org notification service,
+// which GNOME, KDE, dunst, mako and most other desktops implement. The
+// server reports the button the user chose with an ActionInvoked signal.
+use gofer_core::notifier::{ActionHandler, Button, Notifier, NotifyError};
+use log::info;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::message::Type;
+use zbus::zvariant::Value;
+use zbus::{MatchRule, Message};
+
+const NAME: &str = "D-Bus";
+
+const DESTINATION: &str = "org.freedesktop.Notifications";
+const PATH: &str = "/org/freedesktop/Notifications";
+const INTERFACE: &str = "org.freedesktop.Notifications";
+
+// Let the notification server decide how long to show a notification
+const DEFAULT_TIMEOUT: i32 = -1;
+
+fn error(e: impl std::fmt::Display) -> NotifyError {
+    NotifyError::new(NAME, e)
+}
+
+/// The buttons of a notification that is still shown.
+struct Pending {
+    buttons: Vec<Button>,
+    on_action: ActionHandler,
+}
+
+type PendingMap = Arc<Mutex<HashMap<u32, Pending>>>;
+
+pub struct DbusNotifier {
+    connection: Connection,
+    /// Notifications with buttons, by the id the server gave them.
+    pending: PendingMap,
+}
+
+impl DbusNotifier {
+    /// Connects to the bus at `address`, such as a private bus for testing,
+    /// or to the session bus.
+    pub fn connect(address: Option<&str>) -> Result<Self, NotifyError> {
+        let connection = match address {
+            Some(address) => zbus::blocking::connection::Builder::address(address)
+                .and_then(|builder| builder.build()),
+            None => Connection::session(),
+        };
+        let connection = connection.map_err(error)?;
+        let pending = PendingMap::default();
+        listen(&connection, pending.clone())?;
+        Ok(DbusNotifier {
+            connection,
+            pending,
+        })
+    }
+
+    /// Sends a notification with `actions`, pairs of keys and labels, and
+    /// returns its id.
+    fn send(&self, title: &str, message: &str, actions: Vec<&str>) -> Result<u32, NotifyError> {
+        let hints: HashMap<&str, Value> = HashMap::new();
+        let reply = self
+            .connection
+            .call_method(
+                Some(DESTINATION),
+                PATH,
+                Some(INTERFACE),
+                "Notify",
+                &(
+                    "Gofer2",
+                    0u32,
+                    "",
+                    title,
+                    message,
+                    actions,
+                    hints,
+                    DEFAULT_TIMEOUT,
+                ),
+            )
+            .map_err(error)?;
+        reply.body().deserialize().map_err(error)
+    }
+}
+
+/// Watches for the signals of the notification server on a thread of its
+/// own, and calls the handler of the button the user chose.
+fn listen(connection: &Connection, pending: PendingMap) -> Result<(), NotifyError> {
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .interface(INTERFACE)
+        .and_then(|rule| rule.path(PATH))
+        .map_err(error)?
+        .build();
+    let messages = MessageIterator::for_match_rule(rule, connection, None).map_err(error)?;
+    std::thread::spawn(move || {
+        for message in messages {
+            match message {
+                Ok(message) => handle_signal(&message, &pending),
+                Err(e) => info!("{}", error(e)),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle_signal(message: &Message, pending: &PendingMap) {
+    let header = message.header();
+    let body = message.body();
+    match header.member().map(|member| member.as_str()) {
+        Some("ActionInvoked") => {
+            let Ok((id, key)) = body.deserialize::<(u32, &str)>() else {
+                return;
+            };
+            // Release the lock before running the action
+            let Some(notification) = pending.lock().unwrap().remove(&id) else {
+                return;
+            };
+            if let Some(button) = notification.buttons.iter().find(|b| b.key == key) {
+                info!("Notification action chosen: {}", button.label);
+                (notification.on_action)(button.action.clone());
+            }
+        }
+        Some("NotificationClosed") => {
+            if let Ok((id, _reason)) = body.deserialize::<(u32, u32)>() {
+                pending.lock().unwrap().remove(&id);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl Notifier for DbusNotifier {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn notify(&mut self, title: &str, message: &str) -> Result<(), NotifyError> {
+        self.send(title, message, Vec::new())?;
+        Ok(())
+    }
+
+    fn notify_with_buttons(
+        &mut self,
+        title: &str,
+        message: &str,
+        buttons: &[Button],
+        on_action: ActionHandler,
+    ) -> Result<(), NotifyError> {
+        let actions = buttons.iter().flat_map(|b| [b.key, b.label]).collect();
+        // Notify blocks on the server, so the listener must not wait on the
+        // lock meanwhile. An answer can only come from the user once the
+        // notification is on screen, well after the id is stored.
+        let id = self.send(title, message, actions)?;
+        self.pending.lock().unwrap().insert(
+            id,
+            Pending {
+                buttons: buttons.to_vec(),
+                on_action,
+            },
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gofer_core::notifier::{self, Action};
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    /// A private bus, stopped when dropped.
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        /// Starts a bus, or returns `None` if dbus-daemon is not installed.
+        fn start() -> Option<Self> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?)
+                .read_line(&mut address)
+                .ok()?;
+            Some(PrivateBus {
+                daemon,
+                address: address.trim().to_string(),
+            })
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    /// What the fake server was sent: the summary, the body and the action
+    /// keys and labels.
+    type Shown = Arc<Mutex<Vec<(String, String, Vec<String>)>>>;
+
+    /// Records the notifications it is sent.
+    struct FakeServer {
+        shown: Shown,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl FakeServer {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            _app_name: &str,
+            _replaces_id: u32,
+            _app_icon: &str,
+            summary: &str,
+            body: &str,
+            actions: Vec<String>,
+            _hints: HashMap<&str, Value<'_>>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let mut shown = self.shown.lock().unwrap();
+            shown.push((summary.to_string(), body.to_string(), actions));
+            shown.len() as u32
+        }
+    }
+
+    /// Serves a fake notification server on `bus`.
+    fn serve(bus: &PrivateBus) -> (Connection, Shown) {
+        let shown = Shown::default();
+        let server = zbus::blocking::connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .name(DESTINATION)
+            .unwrap()
+            .serve_at(
+                PATH,
+                FakeServer {
+                    shown: shown.clone(),
+                },
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        (server, shown)
+    }
+
+    #[test]
+    fn notification_reaches_the_server() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+        let (_server, shown) = serve(&bus);
+
+        let mut notifier = DbusNotifier::connect(Some(&bus.address)).unwrap();
+        notifier
+            .notify("English to French", "hello → bonjour")
+            .unwrap();
+        assert_eq!(
+            *shown.lock().unwrap(),
+            vec![(
+                "English to French".to_string(),
+                "hello → bonjour".to_string(),
+                Vec::new()
+            )]
+        );
+    }
+
+    #[test]
+    fn chosen_button_runs_its_action() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+        let (server, shown) = serve(&bus);
+
+        let mut notifier = DbusNotifier::connect(Some(&bus.address)).unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let on_action: ActionHandler = Arc::new(move |action| sender.send(action).unwrap());
+        let buttons = notifier::lookup_buttons("hello", "bonjour", false);
+        notifier
+            .notify_with_buttons("English to French", "hello → bonjour", &buttons, on_action)
+            .unwrap();
+        assert_eq!(
+            shown.lock().unwrap()[0].2,
+            vec![
+                "copy-translation",
+                "Copy translation",
+                "copy-source",
+                "Copy source"
+            ]
+        );
+
+        // Another notification's button, then ours
+        for id in [7u32, 1] {
+            server
+                .emit_signal(
+                    None::<()>,
+                    PATH,
+                    INTERFACE,
+                    "ActionInvoked",
+                    &(id, "copy-source"),
+                )
+                .unwrap();
+        }
+        let action = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(action, Action::Copy("hello".to_string()));
+        assert!(notifier.pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn missing_server_is_an_error() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+        let mut notifier = DbusNotifier::connect(Some(&bus.address)).unwrap();
+        let error = notifier.notify("Title", "message").unwrap_err();
+        assert_eq!(error.notifier, "D-Bus");
+    }
+}
@@ -0,0 +1,329 @@
+// src/linux/mod.rs
+//
+// The Linux counterpart of the menu bar app: watches the clipboard, or the
+// PRIMARY selection, of the running X11 or Wayland session and shows
+// lookups as desktop notifications, or on stdout without a desktop.
+use crate::notification;
+use crate::{CONFIG, MISSES, STORE};
+use gofer_core::clipboard::{ClipboardBackend, ClipboardError};
+use gofer_core::config::Config;
+use gofer_core::data;
+use gofer_core::misses::timestamp_now;
+use gofer_core::notifier::Action;
+use gofer_core::trigger::{
+    SelectionDetector, TriggerAction, TriggerDetector, TriggerOutput, TriggerSource,
+};
+use gofer_core::watch::MappingWatcher;
+use log::info;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+mod dbus;
+mod wayland;
+mod x11;
+
+pub use dbus::DbusNotifier;
+
+// How often the clipboard is checked when the backend cannot notify us,
+// and how often a pending double copy or a settling selection is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The selections X11 and Wayland keep.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Selection {
+    /// The clipboard, filled by copying.
+    Clipboard,
+    /// The PRIMARY selection, filled by selecting text and pasted with the
+    /// middle mouse button.
+    Primary,
+}
+
+/// Connects to `selection` in the running session: Wayland when
+/// `$WAYLAND_DISPLAY` is set, falling back to X11 (or XWayland).
+pub fn connect_clipboard(
+    selection: Selection,
+) -> Result<Box<dyn ClipboardBackend>, ClipboardError> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        match wayland::WaylandClipboard::connect(selection) {
+            Ok(clipboard) => return Ok(Box::new(clipboard)),
+            Err(e) => eprintln!("gofer2: {}; trying X11", e),
+        }
+    }
+    Ok(Box::new(x11::X11Clipboard::connect(selection)?))
+}
+
+/// Loads the mappings into [`STORE`] and reports any problems on stderr.
+/// A broken config file keeps the current mappings and settings.
+fn load_mappings(app_dir: &Path, user_dir: Option<&Path>) {
+    let config = match Config::load_from_dir(user_dir) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("gofer2: {}; keeping the current mappings", e);
+            return;
+        }
+    };
+
+    let diagnostics = STORE.reload(app_dir, user_dir, &config.mappings);
+    *CONFIG.write().unwrap() = config;
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    if !diagnostics.is_empty() {
+        eprintln!("{}", data::summarize_diagnostics(&diagnostics));
+    }
+}
+
+/// Shows the translations of `text` and returns the first one.
+fn look_up(text: &str) -> Option<String> {
+    let store = STORE.current();
+    let miss_config = CONFIG.read().unwrap().misses.clone();
+    let candidates = MISSES.look_up(&store, text, &miss_config, timestamp_now());
+    let Some(mapping) = candidates.first() else {
+        notification::show_miss(text, miss_config.notify);
+        return None;
+    };
+    notification::show_lookup(text, &candidates);
+    Some(mapping.translations[0].value.clone())
+}
+
+/// Looks up the text of a detected gesture and carries out its action.
+fn handle_trigger(
+    clipboard: &mut dyn ClipboardBackend,
+    detector: &mut TriggerDetector,
+    text: &str,
+    action: TriggerAction,
+) -> Result<(), ClipboardError> {
+    if action == TriggerAction::None {
+        info!("No action configured for this gesture");
+        return Ok(());
+    }
+
+    let translation = look_up(text);
+    if let (TriggerAction::Replace, Some(translation)) = (action, translation) {
+        // Our own write must not count as a copy
+        clipboard.write_text(&translation)?;
+        detector.ignore_change(clipboard.change_token()?);
+    }
+    Ok(())
+}
+
+/// Carries out the notification buttons chosen since the last call, like
+/// choosing an item in the macOS menu. Returns the clipboard's change token
+/// after our own writes, if there were any.
+fn perform_actions(
+    clipboard: &mut dyn ClipboardBackend,
+    actions: &Receiver<Action>,
+) -> Result<Option<i64>, ClipboardError> {
+    let mut written = None;
+    for action in actions.try_iter() {
+        match action {
+            Action::Copy(text) => {
+                clipboard.write_text(&text)?;
+                info!(
+                    "Copied {} characters to the clipboard",
+                    text.chars().count()
+                );
+                written = Some(clipboard.change_token()?);
+            }
+            Action::Search(_) => info!("No search window to open"),
+        }
+    }
+    Ok(written)
+}
+
+/// Looks up the texts copied with a copy gesture. Only returns on errors.
+fn watch_clipboard(
+    clipboard: &mut dyn ClipboardBackend,
+    actions: &Receiver<Action>,
+) -> Result<(), ClipboardError> {
+    let mut detector = TriggerDetector::new();
+    loop {
+        // Copying from a notification must not count as a copy either
+        if let Some(change_token) = perform_actions(clipboard, actions)? {
+            detector.ignore_change(change_token);
+        }
+
+        let trigger_config = CONFIG.read().unwrap().trigger.clone();
+        detector.set_gesture(trigger_config.gesture());
+
+        let change_token = clipboard.change_token()?;
+        // Only read the text if the clipboard has changed; polling regardless
+        // lets a pending double copy trigger once its window has passed
+        let text = if detector.has_changed(change_token) {
+            read_text(clipboard)
+        } else {
+            None
+        };
+        if let Some(TriggerOutput::Triggered { text, copies }) = detector.poll(change_token, text) {
+            let action = trigger_config.action(copies);
+            if let Err(e) = handle_trigger(clipboard, &mut detector, &text, action) {
+                eprintln!("gofer2: {}", e);
+            }
+        }
+
+        clipboard.wait_for_change(POLL_INTERVAL)?;
+    }
+}
+
+/// Looks up the texts left selected for the dwell time. Only returns on
+/// errors.
+fn watch_selection(
+    selection: &mut dyn ClipboardBackend,
+    clipboard: &mut dyn ClipboardBackend,
+    actions: &Receiver<Action>,
+) -> Result<(), ClipboardError> {
+    let mut detector = SelectionDetector::new();
+    loop {
+        perform_actions(clipboard, actions)?;
+        // Keep handing text copied from a notification to the applications
+        // that paste it
+        clipboard.change_token()?;
+
+        detector.set_dwell(CONFIG.read().unwrap().trigger.dwell());
+
+        let change_token = selection.change_token()?;
+        // The text is read again while the selection settles, since it can
+        // grow without a new change being reported
+        let text = if detector.needs_text(change_token) {
+            read_text(selection)
+        } else {
+            None
+        };
+        if let Some(TriggerOutput::Selected { text }) = detector.poll(change_token, text) {
+            look_up(&text);
+        }
+
+        selection.wait_for_change(POLL_INTERVAL)?;
+    }
+}
+
+/// Reads the text, reporting errors as no text.
+fn read_text(clipboard: &mut dyn ClipboardBackend) -> Option<String> {
+    clipboard.read_text().unwrap_or_else(|e| {
+        eprintln!("gofer2: {}", e);
+        None
+    })
+}
+
+/// Watches the clipboard or the selection until the session ends, and
+/// returns the exit status.
+pub fn run() -> i32 {
+    let app_dir = crate::app_dir();
+    let user_dir: Option<PathBuf> = crate::user_dir();
+    crate::logger::init_logger(user_dir.as_deref());
+    load_mappings(&app_dir, user_dir.as_deref());
+
+    // Reload whenever a mapping file is added, edited or removed
+    let watched_dirs: Vec<PathBuf> = std::iter::once(app_dir.clone())
+        .chain(user_dir.clone())
+        .collect();
+    let _watcher = match MappingWatcher::start(&watched_dirs, move || {
+        load_mappings(&app_dir, user_dir.as_deref())
+    }) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            eprintln!("gofer2: Failed to watch mapping directories: {}", e);
+            None
+        }
+    };
+
+    // Notification buttons are carried out by the watch loop, which owns
+    // the clipboard
+    let (sender, actions) = mpsc::channel();
+    notification::set_action_handler(Arc::new(move |action| {
+        // Fails only once the loop has stopped
+        let _ = sender.send(action);
+    }));
+
+    // The clipboard is also needed in selection mode, to copy from
+    // notifications
+    let mut clipboard = match connect_clipboard(Selection::Clipboard) {
+        Ok(clipboard) => clipboard,
+        Err(e) => {
+            eprintln!("gofer2: {}", e);
+            return 1;
+        }
+    };
+
+    // Unlike the other settings, the source only changes on restart
+    let source = CONFIG.read().unwrap().trigger.source;
+    let result = match source {
+        TriggerSource::Clipboard => {
+            eprintln!("Watching the {} clipboard", clipboard.name());
+            watch_clipboard(clipboard.as_mut(), &actions)
+        }
+        TriggerSource::Selection => match connect_clipboard(Selection::Primary) {
+            Ok(mut selection) => {
+                eprintln!("Watching the {} primary selection", selection.name());
+                watch_selection(selection.as_mut(), clipboard.as_mut(), &actions)
+            }
+            Err(e) => Err(e),
+        },
+    };
+    if let Err(e) = result {
+        eprintln!("gofer2: {}", e);
+    }
+    1
+}
+
+/// Checks shared by the clipboard backend tests. They need a running X11
+/// server or Wayland compositor, such as Xvfb or a headless Sway.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Held by each test that uses the session's selections, since the
+    /// backends share them and XWayland keeps X11 and Wayland in sync.
+    pub(super) static SESSION: Mutex<()> = Mutex::new(());
+
+    /// Text that no other client is likely to have copied.
+    fn unique_text(label: &str) -> String {
+        format!("gofer2 {} test {}", label, std::process::id())
+    }
+
+    /// Writes to `clipboard` and reads the text back from it.
+    pub(super) fn check_round_trip(clipboard: &mut dyn ClipboardBackend) {
+        let text = unique_text("round trip");
+        let before = clipboard.change_token().unwrap();
+        clipboard.write_text(&text).unwrap();
+        assert!(clipboard.change_token().unwrap() > before);
+        assert_eq!(clipboard.read_text().unwrap(), Some(text));
+    }
+
+    /// Writes with a second client on another thread, which serves the text
+    /// until the reader has checked it.
+    pub(super) fn check_other_client_write<F>(reader: &mut dyn ClipboardBackend, connect_writer: F)
+    where
+        F: FnOnce() -> Box<dyn ClipboardBackend> + Send + 'static,
+    {
+        let text = unique_text("other client");
+        let before = reader.change_token().unwrap();
+
+        let (written_tx, written) = mpsc::channel();
+        let (done, done_rx) = mpsc::channel::<()>();
+        let writer = {
+            let text = text.clone();
+            thread::spawn(move || {
+                let mut writer = connect_writer();
+                writer.write_text(&text).unwrap();
+                written_tx.send(()).unwrap();
+                while done_rx.try_recv().is_err() {
+                    writer.wait_for_change(POLL_INTERVAL).unwrap();
+                }
+            })
+        };
+        written.recv().unwrap();
+
+        reader.wait_for_change(Duration::from_secs(1)).unwrap();
+        assert!(reader.change_token().unwrap() > before);
+        assert_eq!(reader.read_text().unwrap(), Some(text));
+
+        done.send(()).unwrap();
+        writer.join().unwrap();
+    }
+}
@@ -0,0 +1,444 @@
+// src/linux/wayland.rs
+//
+// The Wayland clipboard and primary selection, through the
+// wlr-data-control protocol that wlroots-based compositors (Sway,
+// Hyprland, ...) and KDE offer to clipboard managers. Regular clients only
+// see the clipboard while they have keyboard focus, which a background tool
+// never has.
+use super::Selection;
+use gofer_core::clipboard::{ClipboardBackend, ClipboardError};
+use log::info;
+use rustix::event::{PollFd, PollFlags, Timespec, poll};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::{AsFd, OwnedFd};
+use std::thread;
+use std::time::{Duration, Instant};
+use wayland_client::backend::ObjectId;
+use wayland_client::globals::{GlobalListContents, registry_queue_init};
+use wayland_client::protocol::{wl_registry, wl_seat};
+use wayland_client::{Connection, Dispatch, EventQueue, Proxy, QueueHandle, event_created_child};
+use wayland_protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+    zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+};
+
+const NAME: &str = "Wayland";
+
+// How long to wait for the selection owner to send its text
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+// How long a client asking for our text may stall before we give up on it
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Text types in order of preference
+const TEXT_MIME_TYPES: [&str; 5] = [
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "STRING",
+    "TEXT",
+];
+
+fn error(e: impl std::fmt::Display) -> ClipboardError {
+    ClipboardError::new(NAME, e)
+}
+
+/// What the event handlers know about the clipboard.
+struct State {
+    /// The selection whose events are kept; offers for the other one are
+    /// destroyed straight away.
+    watched: Selection,
+    /// Mime types announced for each live offer.
+    offers: HashMap<ObjectId, Vec<String>>,
+    /// The offer for the current contents of the watched selection, if any.
+    selection: Option<ZwlrDataControlOfferV1>,
+    /// Counts selection events.
+    changes: i64,
+    /// The text we serve while our source is the selection.
+    owned_text: Option<String>,
+    source: Option<ZwlrDataControlSourceV1>,
+}
+
+pub struct WaylandClipboard {
+    conn: Connection,
+    queue: EventQueue<State>,
+    state: State,
+    manager: ZwlrDataControlManagerV1,
+    device: ZwlrDataControlDeviceV1,
+}
+
+impl WaylandClipboard {
+    /// Connects to the compositor named by `$WAYLAND_DISPLAY` and watches
+    /// `selection`.
+    pub fn connect(selection: Selection) -> Result<Self, ClipboardError> {
+        let conn = Connection::connect_to_env().map_err(error)?;
+        let (globals, mut queue) = registry_queue_init::<State>(&conn).map_err(error)?;
+        let qh = queue.handle();
+
+        let seat: wl_seat::WlSeat = globals.bind(&qh, 1..=1, ()).map_err(error)?;
+        // The primary selection was added in version 2
+        let versions = match selection {
+            Selection::Clipboard => 1..=2,
+            Selection::Primary => 2..=2,
+        };
+        let manager: ZwlrDataControlManagerV1 = globals
+            .bind(&qh, versions, ())
+            .map_err(|e| error(format!("wlr-data-control is not available: {}", e)))?;
+        let device = manager.get_data_device(&seat, &qh, ());
+
+        // Receive the current selection
+        let mut state = State {
+            watched: selection,
+            offers: HashMap::new(),
+            selection: None,
+            changes: 0,
+            owned_text: None,
+            source: None,
+        };
+        queue.roundtrip(&mut state).map_err(error)?;
+
+        Ok(WaylandClipboard {
+            conn,
+            queue,
+            state,
+            manager,
+            device,
+        })
+    }
+
+    /// Handles the events that have arrived, waiting up to `timeout` for
+    /// the first one. Returns whether the selection changed.
+    fn process_events(&mut self, timeout: Duration) -> Result<bool, ClipboardError> {
+        let before = self.state.changes;
+        self.queue
+            .dispatch_pending(&mut self.state)
+            .map_err(error)?;
+        self.queue.flush().map_err(error)?;
+
+        if self.state.changes == before {
+            if let Some(guard) = self.queue.prepare_read()
+                && wait_readable(guard.connection_fd(), timeout)?
+            {
+                guard.read().map_err(error)?;
+            }
+            self.queue
+                .dispatch_pending(&mut self.state)
+                .map_err(error)?;
+        }
+        Ok(self.state.changes != before)
+    }
+}
+
+/// Waits until `fd` has data to read, or `timeout` passes.
+fn wait_readable(fd: impl AsFd, timeout: Duration) -> Result<bool, ClipboardError> {
+    let mut fds = [PollFd::new(&fd, PollFlags::IN)];
+    let timeout = Timespec::try_from(timeout).map_err(error)?;
+    match poll(&mut fds, Some(&timeout)) {
+        Ok(ready) => Ok(ready > 0),
+        Err(rustix::io::Errno::INTR) => Ok(false),
+        Err(e) => Err(error(e)),
+    }
+}
+
+impl ClipboardBackend for WaylandClipboard {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn read_text(&mut self) -> Result<Option<String>, ClipboardError> {
+        self.process_events(Duration::ZERO)?;
+        // Asking ourselves would wait for an answer we can never send
+        if let Some(text) = &self.state.owned_text {
+            return Ok(Some(text.clone()));
+        }
+
+        let Some(offer) = &self.state.selection else {
+            return Ok(None);
+        };
+        let mime_types = self.state.offers.get(&offer.id());
+        let Some(mime_type) = TEXT_MIME_TYPES.iter().find(|mime_type| {
+            mime_types.is_some_and(|types| types.iter().any(|t| t == *mime_type))
+        }) else {
+            return Ok(None);
+        };
+
+        // The owner writes the text into a pipe
+        let (reader, writer) = std::io::pipe().map_err(error)?;
+        offer.receive(mime_type.to_string(), writer.as_fd());
+        self.conn.flush().map_err(error)?;
+        drop(writer);
+
+        let mut reader = reader;
+        let mut bytes = Vec::new();
+        let mut buffer = [0; 4096];
+        let deadline = Instant::now() + READ_TIMEOUT;
+        loop {
+            let now = Instant::now();
+            if now >= deadline || !wait_readable(&reader, deadline - now)? {
+                return Err(error("timed out waiting for the selection owner"));
+            }
+            match reader.read(&mut buffer).map_err(error)? {
+                0 => break,
+                n => bytes.extend_from_slice(&buffer[..n]),
+            }
+        }
+        Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        let qh = self.queue.handle();
+        let source = self.manager.create_data_source(&qh, ());
+        for mime_type in TEXT_MIME_TYPES {
+            source.offer(mime_type.to_string());
+        }
+        match self.state.watched {
+            Selection::Clipboard => self.device.set_selection(Some(&source)),
+            Selection::Primary => self.device.set_primary_selection(Some(&source)),
+        }
+
+        if let Some(previous) = self.state.source.replace(source) {
+            previous.destroy();
+        }
+        self.state.owned_text = Some(text.to_string());
+
+        // Make sure our own selection event has arrived
+        self.queue.roundtrip(&mut self.state).map_err(error)?;
+        Ok(())
+    }
+
+    fn change_token(&mut self) -> Result<i64, ClipboardError> {
+        self.process_events(Duration::ZERO)?;
+        Ok(self.state.changes)
+    }
+
+    fn wait_for_change(&mut self, timeout: Duration) -> Result<bool, ClipboardError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let now = Instant::now();
+            if self.process_events(deadline.saturating_duration_since(now))? {
+                return Ok(true);
+            }
+            if Instant::now() >= deadline {
+                return Ok(false);
+            }
+        }
+    }
+}
+
+impl State {
+    /// Records the new offer for `selection`, or destroys it if that is not
+    /// the selection being watched.
+    fn set_offer(&mut self, selection: Selection, offer: Option<ZwlrDataControlOfferV1>) {
+        if selection != self.watched {
+            if let Some(offer) = offer {
+                self.offers.remove(&offer.id());
+                offer.destroy();
+            }
+            return;
+        }
+        if let Some(previous) = self.selection.take() {
+            self.offers.remove(&previous.id());
+            previous.destroy();
+        }
+        self.selection = offer;
+        self.changes += 1;
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _state: &mut Self,
+        _registry: &wl_registry::WlRegistry,
+        _event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _seat: &wl_seat::WlSeat,
+        _event: wl_seat::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrDataControlManagerV1, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _manager: &ZwlrDataControlManagerV1,
+        _event: <ZwlrDataControlManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _device: &ZwlrDataControlDeviceV1,
+        event: zwlr_data_control_device_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_data_control_device_v1::Event::DataOffer { id } => {
+                state.offers.insert(id.id(), Vec::new());
+            }
+            zwlr_data_control_device_v1::Event::Selection { id } => {
+                state.set_offer(Selection::Clipboard, id);
+            }
+            zwlr_data_control_device_v1::Event::PrimarySelection { id } => {
+                state.set_offer(Selection::Primary, id);
+            }
+            zwlr_data_control_device_v1::Event::Finished => {
+                info!("The compositor stopped sending clipboard events");
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(State, ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrDataControlOfferV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        offer: &ZwlrDataControlOfferV1,
+        event: zwlr_data_control_offer_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zwlr_data_control_offer_v1::Event::Offer { mime_type } = event {
+            state.offers.entry(offer.id()).or_default().push(mime_type);
+        }
+    }
+}
+
+impl Dispatch<ZwlrDataControlSourceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        source: &ZwlrDataControlSourceV1,
+        event: zwlr_data_control_source_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_data_control_source_v1::Event::Send { fd, .. } => {
+                if let Some(text) = &state.owned_text {
+                    send_text(fd, text);
+                }
+            }
+            zwlr_data_control_source_v1::Event::Cancelled => {
+                // Another client took over the clipboard
+                if state.source.as_ref() == Some(source) {
+                    state.source = None;
+                    state.owned_text = None;
+                }
+                source.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Writes our text to a client that asked for the selection. A large text
+/// fills the pipe until the client reads it, so the write runs on its own
+/// thread rather than blocking event dispatch.
+fn send_text(fd: OwnedFd, text: &str) {
+    let text = text.to_string();
+    thread::spawn(move || {
+        if let Err(e) = write_nonblocking(fd, text.as_bytes()) {
+            info!("Failed to send clipboard text: {}", e);
+        }
+    });
+}
+
+/// Writes `bytes` to `fd`, giving up once the reader stalls for longer than
+/// `SEND_TIMEOUT`.
+fn write_nonblocking(fd: OwnedFd, mut bytes: &[u8]) -> Result<(), ClipboardError> {
+    rustix::io::ioctl_fionbio(&fd, true).map_err(error)?;
+    let mut file = File::from(fd);
+    while !bytes.is_empty() {
+        match file.write(bytes) {
+            Ok(0) => return Err(error("the client closed the pipe")),
+            Ok(n) => bytes = &bytes[n..],
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                if !wait_writable(&file, SEND_TIMEOUT)? {
+                    return Err(error("timed out waiting for the client to read"));
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(error(e)),
+        }
+    }
+    Ok(())
+}
+
+/// Waits until `fd` can take more data, or `timeout` passes.
+fn wait_writable(fd: impl AsFd, timeout: Duration) -> Result<bool, ClipboardError> {
+    let mut fds = [PollFd::new(&fd, PollFlags::OUT)];
+    let timeout = Timespec::try_from(timeout).map_err(error)?;
+    match poll(&mut fds, Some(&timeout)) {
+        Ok(ready) => Ok(ready > 0),
+        Err(rustix::io::Errno::INTR) => Ok(true),
+        Err(e) => Err(error(e)),
+    }
+}
+
+/// These run against the compositor in `$WAYLAND_DISPLAY`, e.g. a headless
+/// Sway, so they are ignored by default.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linux::tests::{SESSION, check_other_client_write, check_round_trip};
+
+    #[test]
+    fn text_larger_than_the_pipe_is_sent_in_full() {
+        let text = "x".repeat(1 << 20);
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        send_text(OwnedFd::from(writer), &text);
+        let mut received = String::new();
+        reader.read_to_string(&mut received).unwrap();
+        assert_eq!(received.len(), text.len());
+    }
+
+    #[test]
+    #[ignore = "needs a wlr-data-control compositor, e.g. a headless Sway"]
+    fn written_text_is_read_back() {
+        let _session = SESSION.lock().unwrap();
+        for selection in [Selection::Clipboard, Selection::Primary] {
+            let mut clipboard = WaylandClipboard::connect(selection).unwrap();
+            check_round_trip(&mut clipboard);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a wlr-data-control compositor, e.g. a headless Sway"]
+    fn text_written_by_another_client_is_read() {
+        let _session = SESSION.lock().unwrap();
+        for selection in [Selection::Clipboard, Selection::Primary] {
+            let mut reader = WaylandClipboard::connect(selection).unwrap();
+            check_other_client_write(&mut reader, move || {
+                Box::new(WaylandClipboard::connect(selection).unwrap())
+            });
+        }
+    }
+}
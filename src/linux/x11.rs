@@ -0,0 +1,386 @@
+// src/linux/x11.rs
+//
+// The X11 clipboard and PRIMARY selection, watched with the XFixes
+// extension. X11 has no clipboard storage: the owner of a selection hands the data to each
+// client that asks for it, so after writing we keep serving our text until
+// another client takes the selection over.
+use super::Selection;
+use gofer_core::clipboard::{ClipboardBackend, ClipboardError};
+use rustix::event::{PollFd, PollFlags, Timespec, poll};
+use std::time::{Duration, Instant};
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::Event;
+use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEventMask};
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode,
+    SELECTION_NOTIFY_EVENT, SelectionNotifyEvent, SelectionRequestEvent, Window, WindowClass,
+};
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::{COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE};
+
+const NAME: &str = "X11";
+
+// How long to wait for the selection owner to send its text
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Size of a ChangeProperty request without its data
+const CHANGE_PROPERTY_HEADER: usize = 24;
+
+struct Atoms {
+    targets: Atom,
+    utf8_string: Atom,
+    text: Atom,
+    incr: Atom,
+    /// Property on our window that selection contents are delivered to.
+    transfer: Atom,
+}
+
+pub struct X11Clipboard {
+    conn: RustConnection,
+    window: Window,
+    atoms: Atoms,
+    /// The CLIPBOARD or PRIMARY atom.
+    selection: Atom,
+    /// Counts XFixes selection-owner changes.
+    changes: i64,
+    /// The text we serve while we own the selection.
+    owned_text: Option<String>,
+}
+
+fn error(e: impl std::fmt::Display) -> ClipboardError {
+    ClipboardError::new(NAME, e)
+}
+
+impl X11Clipboard {
+    /// Connects to the display named by `$DISPLAY` and watches `selection`.
+    pub fn connect(selection: Selection) -> Result<Self, ClipboardError> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(error)?;
+        if conn
+            .extension_information(x11rb::protocol::xfixes::X11_EXTENSION_NAME)
+            .map_err(error)?
+            .is_none()
+        {
+            return Err(error("the XFixes extension is not available"));
+        }
+        conn.xfixes_query_version(5, 0)
+            .map_err(error)?
+            .reply()
+            .map_err(error)?;
+
+        let intern = |name: &[u8]| -> Result<Atom, ClipboardError> {
+            Ok(conn
+                .intern_atom(false, name)
+                .map_err(error)?
+                .reply()
+                .map_err(error)?
+                .atom)
+        };
+        let selection = match selection {
+            Selection::Clipboard => intern(b"CLIPBOARD")?,
+            Selection::Primary => AtomEnum::PRIMARY.into(),
+        };
+        let atoms = Atoms {
+            targets: intern(b"TARGETS")?,
+            utf8_string: intern(b"UTF8_STRING")?,
+            text: intern(b"TEXT")?,
+            incr: intern(b"INCR")?,
+            transfer: intern(b"GOFER_SELECTION")?,
+        };
+
+        // An invisible window to own the selection and receive its contents
+        let root = conn.setup().roots[screen_num].root;
+        let window = conn.generate_id().map_err(error)?;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            COPY_FROM_PARENT,
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )
+        .map_err(error)?;
+        conn.xfixes_select_selection_input(
+            window,
+            selection,
+            SelectionEventMask::SET_SELECTION_OWNER
+                | SelectionEventMask::SELECTION_WINDOW_DESTROY
+                | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+        )
+        .map_err(error)?;
+        conn.flush().map_err(error)?;
+
+        Ok(X11Clipboard {
+            conn,
+            window,
+            atoms,
+            selection,
+            changes: 0,
+            owned_text: None,
+        })
+    }
+
+    /// Handles every event that has already arrived and returns whether the
+    /// selection changed.
+    fn process_events(&mut self) -> Result<bool, ClipboardError> {
+        let mut changed = false;
+        while let Some(event) = self.conn.poll_for_event().map_err(error)? {
+            changed |= self.handle_event(event)?;
+        }
+        Ok(changed)
+    }
+
+    /// Handles one event and returns whether the selection changed.
+    fn handle_event(&mut self, event: Event) -> Result<bool, ClipboardError> {
+        match event {
+            Event::XfixesSelectionNotify(event) if event.selection == self.selection => {
+                self.changes += 1;
+                return Ok(true);
+            }
+            Event::SelectionRequest(request) => self.answer_request(&request)?,
+            Event::SelectionClear(event) if event.selection == self.selection => {
+                self.owned_text = None;
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    /// Sends our text to a client that asked for the selection.
+    fn answer_request(&mut self, request: &SelectionRequestEvent) -> Result<(), ClipboardError> {
+        let mut property = request.property;
+        // Obsolete clients leave the property unset and expect the target
+        if property == NONE {
+            property = request.target;
+        }
+
+        let text_targets = [
+            self.atoms.utf8_string,
+            self.atoms.text,
+            AtomEnum::STRING.into(),
+        ];
+        match &self.owned_text {
+            Some(_) if request.target == self.atoms.targets => {
+                let mut targets = vec![self.atoms.targets];
+                targets.extend(text_targets);
+                self.conn
+                    .change_property32(
+                        PropMode::REPLACE,
+                        request.requestor,
+                        property,
+                        AtomEnum::ATOM,
+                        &targets,
+                    )
+                    .map_err(error)?;
+            }
+            Some(text) if text_targets.contains(&request.target) => {
+                self.conn
+                    .change_property8(
+                        PropMode::REPLACE,
+                        request.requestor,
+                        property,
+                        request.target,
+                        text.as_bytes(),
+                    )
+                    .map_err(error)?;
+            }
+            // Refuse anything else
+            _ => property = NONE,
+        }
+
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property,
+        };
+        self.conn
+            .send_event(false, request.requestor, EventMask::NO_EVENT, notify)
+            .map_err(error)?;
+        self.conn.flush().map_err(error)?;
+        Ok(())
+    }
+
+    /// Waits until the connection has data to read, or `timeout` passes.
+    fn wait_readable(&self, timeout: Duration) -> Result<bool, ClipboardError> {
+        let stream = self.conn.stream();
+        let mut fds = [PollFd::new(stream, PollFlags::IN)];
+        let timeout = Timespec::try_from(timeout).map_err(error)?;
+        match poll(&mut fds, Some(&timeout)) {
+            Ok(ready) => Ok(ready > 0),
+            Err(rustix::io::Errno::INTR) => Ok(false),
+            Err(e) => Err(error(e)),
+        }
+    }
+}
+
+impl ClipboardBackend for X11Clipboard {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn read_text(&mut self) -> Result<Option<String>, ClipboardError> {
+        self.process_events()?;
+        // Asking ourselves would wait for an answer we can never send
+        if let Some(text) = &self.owned_text {
+            return Ok(Some(text.clone()));
+        }
+
+        self.conn
+            .convert_selection(
+                self.window,
+                self.selection,
+                self.atoms.utf8_string,
+                self.atoms.transfer,
+                CURRENT_TIME,
+            )
+            .map_err(error)?;
+        self.conn.flush().map_err(error)?;
+
+        // Wait for the owner to put the text on our window
+        let deadline = Instant::now() + READ_TIMEOUT;
+        loop {
+            let event = match self.conn.poll_for_event().map_err(error)? {
+                Some(event) => event,
+                None => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(error("timed out waiting for the selection owner"));
+                    }
+                    self.wait_readable(deadline - now)?;
+                    continue;
+                }
+            };
+            let Event::SelectionNotify(notify) = event else {
+                self.handle_event(event)?;
+                continue;
+            };
+            if notify.selection != self.selection {
+                continue;
+            }
+            if notify.property == NONE {
+                // The owner has no text
+                return Ok(None);
+            }
+
+            let reply = self
+                .conn
+                .get_property(
+                    true,
+                    self.window,
+                    self.atoms.transfer,
+                    AtomEnum::ANY,
+                    0,
+                    u32::MAX / 4,
+                )
+                .map_err(error)?
+                .reply()
+                .map_err(error)?;
+            if reply.type_ == self.atoms.incr {
+                return Err(error("incremental transfers are not supported"));
+            }
+            return Ok(Some(String::from_utf8_lossy(&reply.value).into_owned()));
+        }
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        // We serve the text in a single property change, without INCR
+        let max_len = self
+            .conn
+            .maximum_request_bytes()
+            .saturating_sub(CHANGE_PROPERTY_HEADER);
+        if text.len() > max_len {
+            return Err(error(format!(
+                "{} bytes is more than the {} the server accepts at once",
+                text.len(),
+                max_len
+            )));
+        }
+
+        self.owned_text = Some(text.to_string());
+        self.conn
+            .set_selection_owner(self.window, self.selection, CURRENT_TIME)
+            .map_err(error)?;
+        let owner = self
+            .conn
+            .get_selection_owner(self.selection)
+            .map_err(error)?
+            .reply()
+            .map_err(error)?
+            .owner;
+        if owner != self.window {
+            self.owned_text = None;
+            return Err(error("another client kept the selection"));
+        }
+        // The round trip above means our own change has been reported
+        self.process_events()?;
+        Ok(())
+    }
+
+    fn change_token(&mut self) -> Result<i64, ClipboardError> {
+        self.process_events()?;
+        Ok(self.changes)
+    }
+
+    fn wait_for_change(&mut self, timeout: Duration) -> Result<bool, ClipboardError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.process_events()? {
+                return Ok(true);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+            self.wait_readable(deadline - now)?;
+        }
+    }
+}
+
+/// These run against the display in `$DISPLAY`, e.g. under `xvfb-run`, so
+/// they are ignored by default.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linux::tests::{SESSION, check_other_client_write, check_round_trip};
+
+    #[test]
+    #[ignore = "needs an X11 display, e.g. xvfb-run cargo test -- --ignored"]
+    fn written_text_is_read_back() {
+        let _session = SESSION.lock().unwrap();
+        for selection in [Selection::Clipboard, Selection::Primary] {
+            let mut clipboard = X11Clipboard::connect(selection).unwrap();
+            check_round_trip(&mut clipboard);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs an X11 display, e.g. xvfb-run cargo test -- --ignored"]
+    fn text_written_by_another_client_is_read() {
+        let _session = SESSION.lock().unwrap();
+        for selection in [Selection::Clipboard, Selection::Primary] {
+            let mut reader = X11Clipboard::connect(selection).unwrap();
+            check_other_client_write(&mut reader, move || {
+                Box::new(X11Clipboard::connect(selection).unwrap())
+            });
+        }
+    }
+
+    #[test]
+    #[ignore = "needs an X11 display, e.g. xvfb-run cargo test -- --ignored"]
+    fn oversized_text_is_rejected() {
+        let _session = SESSION.lock().unwrap();
+        let mut clipboard = X11Clipboard::connect(Selection::Clipboard).unwrap();
+        let text = "x".repeat(clipboard.conn.maximum_request_bytes());
+        assert!(clipboard.write_text(&text).is_err());
+        assert_eq!(clipboard.owned_text, None);
+    }
+}
@@ -1,10 +1,69 @@
-use log::LevelFilter;
-use oslog::OsLogger;
-
-/// Initializes the logger for the application.
-pub fn init_logger() {
-    OsLogger::new("com.1000ants.gofer2")
-        .level_filter(LevelFilter::Debug)
-        .init()
-        .unwrap();
+// src/logger.rs
+//
+// Sets up logging from the `[log]` settings. Logging is set up before the
+// mappings are loaded, so the settings are read here on their own and only
+// take effect on restart.
+use gofer_core::config::{Config, LogConfig};
+use gofer_core::logging::{
+    FileLog, LOG_FILE_NAME, LogFilter, LogSink, Logger, RotatingFile, StderrLog,
+};
+use log::{Log, info};
+use std::path::{Path, PathBuf};
+
+/// The path of the log file, whether or not it has been written yet.
+pub fn log_path() -> Option<PathBuf> {
+    crate::state_dir().map(|dir| dir.join(LOG_FILE_NAME))
+}
+
+/// Initializes the logger for the application. Problems are reported on
+/// stderr and leave out the sinks they affect.
+pub fn init_logger(user_dir: Option<&Path>) {
+    let config = Config::load_from_dir(user_dir).map_or_else(
+        |e| {
+            eprintln!("gofer2: {}", e);
+            LogConfig::default()
+        },
+        |config| config.log,
+    );
+
+    // $RUST_LOG overrides the configured level, as in other Rust programs
+    let spec = std::env::var("RUST_LOG").unwrap_or_else(|_| config.level.clone());
+    let filter = LogFilter::parse(&spec).unwrap_or_else(|e| {
+        eprintln!("gofer2: {}; using {:?}", e, LogConfig::default().level);
+        LogFilter::parse(&LogConfig::default().level).unwrap()
+    });
+
+    let sinks: Vec<Box<dyn Log>> = config
+        .sinks()
+        .into_iter()
+        .filter_map(|sink| open_sink(sink, &config))
+        .collect();
+    if let Err(e) = Logger::new(filter, sinks).init() {
+        eprintln!("gofer2: Failed to set up logging: {}", e);
+        return;
+    }
+    info!("Logging with {:?} to {:?}", spec, config.sinks());
+}
+
+fn open_sink(sink: LogSink, config: &LogConfig) -> Option<Box<dyn Log>> {
+    match sink {
+        LogSink::Stderr => Some(Box::new(StderrLog)),
+        LogSink::File => {
+            let path = log_path()?;
+            match RotatingFile::open(&path, config.max_size_kb * 1024, config.max_files) {
+                Ok(file) => Some(Box::new(FileLog::new(file))),
+                Err(e) => {
+                    eprintln!("gofer2: Failed to open the log file {:?}: {}", path, e);
+                    None
+                }
+            }
+        }
+        #[cfg(target_os = "macos")]
+        LogSink::Oslog => Some(Box::new(oslog::OsLogger::new("com.1000ants.gofer2"))),
+        #[cfg(not(target_os = "macos"))]
+        LogSink::Oslog => {
+            eprintln!("gofer2: The oslog sink is only available on macOS");
+            None
+        }
+    }
 }
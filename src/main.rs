@@ -1,72 +1,90 @@
-use cocoa::appkit::{NSApplication, NSApplicationActivationPolicy};
-use cocoa::base::{id, nil};
-use cocoa::foundation::{NSAutoreleasePool, NSString};
-use log::info;
-use objc::{class, msg_send, sel, sel_impl};
+use gofer_core::config::Config;
+use gofer_core::data::SharedMappingStore;
+use gofer_core::misses::{MISS_LOG_FILE_NAME, SharedMissLog};
 use std::env::home_dir;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 
+mod cli;
+
+#[cfg(target_os = "macos")]
+mod app;
+#[cfg(target_os = "macos")]
 mod clipboard;
-mod data;
 mod logger;
+#[cfg(target_os = "macos")]
 mod menu;
 mod notification;
+#[cfg(target_os = "macos")]
 mod search;
 
-fn main() {
-    // Initialize our logger early on.
-    logger::init_logger();
-    info!("Starting Basic Menu Bar App");
-
-    // Load all CSV mappings from the OSX app resources directory
-    let csv_dir = std::path::PathBuf::from("Contents")
-        .join("Resources")
-        .join("resources");
-    // Load all CSV mappings from the user gofer2 directory
-    let user_dir: Option<PathBuf> = home_dir().map(|home| home.join(".config").join("gofer"));
-
-    match data::load_all_mappings(&csv_dir, user_dir.as_deref()) {
-        Ok(_) => info!("Successfully loaded all mappings"),
-        Err(e) => {
-            let error_msg = format!("{}", e);
-            info!("{}", error_msg);
-            // Show error notification to user
-            notification::show_notification("Mapping Load Error", &error_msg);
-        }
-    }
-
-    // Register search delegates
-    search::register_search_delegates();
+#[cfg(target_os = "linux")]
+mod linux;
 
-    unsafe {
-        let _pool = NSAutoreleasePool::new(nil);
-        let app = NSApplication::sharedApplication(nil);
-        app.setActivationPolicy_(
-            NSApplicationActivationPolicy::NSApplicationActivationPolicyAccessory,
-        );
+lazy_static::lazy_static! {
+    /// The active mapping store, shared by the clipboard monitor and the search window.
+    pub static ref STORE: SharedMappingStore = SharedMappingStore::default();
+    /// The user settings, re-read whenever the mappings are reloaded.
+    pub static ref CONFIG: RwLock<Config> = RwLock::new(Config::default());
+    /// Texts copied without a mapping, logged by the clipboard watchers.
+    pub static ref MISSES: SharedMissLog =
+        SharedMissLog::new(user_dir().map(|dir| dir.join(MISS_LOG_FILE_NAME)));
+}
 
-        // Register our Objective‑C handler class for menu events.
-        let handler_class = menu::register_selector();
-        let handler: id = msg_send![handler_class, new];
+/// The user mapping directory, `~/.config/gofer`, which also holds the
+/// settings and the miss log.
+pub fn user_dir() -> Option<PathBuf> {
+    home_dir().map(|home| home.join(".config").join("gofer"))
+}
 
-        // Create the status bar item with our custom menu.
-        let _status_item = menu::create_status_item(handler);
+/// Where the app keeps its log: `~/Library/Logs/Gofer2` on macOS, and
+/// `$XDG_STATE_HOME/gofer` or `~/.local/state/gofer` elsewhere.
+pub fn state_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "macos") {
+        return home_dir().map(|home| home.join("Library").join("Logs").join("Gofer2"));
+    }
+    match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("gofer")),
+        _ => home_dir().map(|home| home.join(".local").join("state").join("gofer")),
+    }
+}
 
-        // Optionally, you can also listen for app termination notifications.
-        let notification_center: id = msg_send![class!(NSNotificationCenter), defaultCenter];
-        let quit_notification =
-            NSString::alloc(nil).init_str("NSApplicationWillTerminateNotification");
-        let _: () = msg_send![notification_center,
-            addObserver: handler
-            selector: sel!(applicationWillTerminate:)
-            name: quit_notification
-            object: nil
-        ];
+/// The mappings bundled with the app, found from the executable rather than
+/// the working directory: `Contents/Resources/resources` in the app bundle,
+/// whose binary is in `Contents/MacOS`, or `share/gofer` next to the `bin`
+/// directory of an installed binary.
+pub fn app_dir() -> PathBuf {
+    let exe = std::env::current_exe()
+        .and_then(|exe| exe.canonicalize())
+        .unwrap_or_default();
+    let prefix = exe
+        .parent()
+        .and_then(|bin| bin.parent())
+        .unwrap_or(Path::new("/"));
+    if cfg!(target_os = "macos") {
+        prefix.join("Resources").join("resources")
+    } else {
+        prefix.join("share").join("gofer")
+    }
+}
 
-        // Start monitoring the clipboard.
-        clipboard::start_clipboard_monitor();
+fn main() {
+    // Any arguments run a command line instead of the menu bar app. Finder
+    // may pass a process serial number to apps launched from a bundle.
+    let args: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("-psn_"))
+        .collect();
 
-        // Run the application.
-        app.run();
+    #[cfg(target_os = "macos")]
+    if args.is_empty() {
+        app::run();
+        return;
     }
+    #[cfg(target_os = "linux")]
+    if args.is_empty() {
+        std::process::exit(linux::run());
+    }
+
+    std::process::exit(cli::run(&args));
 }
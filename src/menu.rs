@@ -1,27 +1,58 @@
 // src/menu.rs
-use crate::search;
+use crate::CONFIG;
+use crate::clipboard;
+use crate::{logger, notification, search};
 use cocoa::appkit::{NSEventModifierFlags, NSMenu, NSMenuItem, NSStatusBar, NSStatusItem};
-use cocoa::base::{BOOL, YES, id, nil};
+use cocoa::base::{BOOL, NO, YES, id, nil};
 use cocoa::foundation::{NSAutoreleasePool, NSSize, NSString};
+use gofer_core::history::{HISTORY_FILE_NAME, History, HistoryEntry};
+use gofer_core::notifier::Action;
 use log::info;
 use objc::declare::ClassDecl;
 use objc::runtime::{Class, Object, Sel};
 use objc::{class, msg_send, sel, sel_impl};
-use std::path::PathBuf;
-use std::sync::Once;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Once};
 
 static mut STATUS_ITEM: Option<id> = None;
 static INIT: Once = Once::new();
 static mut MENU: Option<id> = None;
 static mut HANDLER: Option<id> = None;
 
+lazy_static::lazy_static! {
+    // Recent lookups, restored into the menu at startup
+    static ref HISTORY: Mutex<History> = Mutex::new(load_history());
+    // Notification buttons chosen on other threads, waiting for the main thread
+    static ref PENDING_ACTIONS: Mutex<Vec<Action>> = Mutex::new(Vec::new());
+}
+
 // Separator
 // About
 // Search
+// Undo Replace
+// Export Misses
+// Clear History
+// Show Log
 // Help
 // Separator
 // Quit
-static STATIC_ITEMS: i64 = 6;
+static STATIC_ITEMS: i64 = 10;
+
+// Longer history texts are shortened in the menu
+const MAX_TITLE_CHARS: usize = 50;
+
+/// Loads the saved history, or starts an empty one if it is not persisted.
+fn load_history() -> History {
+    let config = CONFIG.read().unwrap().history.clone();
+    let path = match crate::user_dir() {
+        Some(dir) if config.persist => dir.join(HISTORY_FILE_NAME),
+        _ => return History::in_memory(config.size),
+    };
+    History::load(&path, config.size).unwrap_or_else(|e| {
+        info!("Failed to load history from {:?}: {}", path, e);
+        History::in_memory(config.size)
+    })
+}
 
 fn get_app_version() -> String {
     env!("CARGO_PKG_VERSION").to_string()
@@ -44,10 +75,13 @@ extern "C" fn show_help_panel(_this: &Object, _cmd: Sel, _sender: id) {
 
         let message = NSString::alloc(nil).init_str(
             "How to use Gofer2:\n\n\
-            1. Add CSV files to ~/.gofer2/ directory with two columns:\n\
-               English,French\n\
-               hello,bonjour\n\
-               goodbye,au revoir\n\n\
+            1. Add CSV files to ~/.config/gofer. The first row is a header\n\
+               naming the source column, then one column per target\n\
+               language. Each target becomes its own translation, and\n\
+               empty cells are skipped:\n\
+               English,French,German\n\
+               hello,bonjour,hallo\n\
+               goodbye,au revoir,tschüss\n\n\
             2. Double-copy (⌘C twice quickly) any text to look up its translation\n\n\
             3. When a translation is found:\n\
                • A notification will appear\n\
@@ -71,6 +105,11 @@ pub fn register_selector() -> *const Class {
             do_action as extern "C" fn(&Object, Sel, id),
         );
 
+        decl.add_method(
+            sel!(performPendingActions:),
+            perform_pending_actions as extern "C" fn(&Object, Sel, id),
+        );
+
         decl.add_method(
             sel!(applicationWillTerminate:),
             application_will_terminate as extern "C" fn(&Object, Sel, id),
@@ -91,42 +130,188 @@ pub fn register_selector() -> *const Class {
             search::show_search_window as extern "C" fn(&Object, Sel, id),
         );
 
-        decl.register()
-    }
-}
+        decl.add_method(
+            sel!(undoReplace:),
+            undo_replace as extern "C" fn(&Object, Sel, id),
+        );
 
-fn copy_to_clipboard(text: &str) {
-    unsafe {
-        let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
-        let _: () = msg_send![pasteboard, clearContents];
+        decl.add_method(
+            sel!(pinEntry:),
+            pin_entry as extern "C" fn(&Object, Sel, id),
+        );
 
-        let ns_string = NSString::alloc(nil).init_str(text);
-        let _: BOOL = msg_send![pasteboard,
-            setString:ns_string
-            forType:NSString::alloc(nil).init_str("public.utf8-plain-text")
-        ];
+        decl.add_method(
+            sel!(unpinEntry:),
+            unpin_entry as extern "C" fn(&Object, Sel, id),
+        );
 
-        info!("Copied to clipboard: {}", text);
+        decl.add_method(
+            sel!(clearHistory:),
+            clear_history as extern "C" fn(&Object, Sel, id),
+        );
+
+        decl.add_method(
+            sel!(exportMisses:),
+            export_misses as extern "C" fn(&Object, Sel, id),
+        );
+
+        decl.add_method(sel!(showLog:), show_log as extern "C" fn(&Object, Sel, id));
+
+        decl.add_method(
+            sel!(validateMenuItem:),
+            validate_menu_item as extern "C" fn(&Object, Sel, id) -> BOOL,
+        );
+
+        decl.register()
     }
 }
 
 extern "C" fn do_action(_this: &Object, _cmd: Sel, item: id) {
     unsafe {
-        let title: id = msg_send![item, title];
-        if title != nil {
-            let c_str = NSString::UTF8String(title);
+        // History items carry the full text to copy as the represented
+        // object; their titles may be shortened
+        let text: id = msg_send![item, representedObject];
+        if text != nil {
+            let c_str = NSString::UTF8String(text);
             if !c_str.is_null() {
                 let text = std::ffi::CStr::from_ptr(c_str)
                     .to_string_lossy()
                     .into_owned();
-                copy_to_clipboard(&text);
+                perform_action(Action::Copy(text));
             }
         }
     }
 }
 
+/// Carries out the action of a menu item or a notification button.
+fn perform_action(action: Action) {
+    match action {
+        Action::Copy(text) => clipboard::copy_text(&text),
+        Action::Search(query) => search::open_search_window(&query),
+    }
+}
+
+/// Carries out an action chosen on another thread, such as a notification
+/// button, on the main thread.
+pub fn perform_action_on_main_thread(action: Action) {
+    PENDING_ACTIONS.lock().unwrap().push(action);
+    unsafe {
+        if let Some(handler) = HANDLER {
+            let _: () = msg_send![handler,
+                performSelectorOnMainThread: sel!(performPendingActions:)
+                withObject: nil
+                waitUntilDone: NO
+            ];
+        }
+    }
+}
+
+extern "C" fn perform_pending_actions(_this: &Object, _cmd: Sel, _sender: id) {
+    let actions = std::mem::take(&mut *PENDING_ACTIONS.lock().unwrap());
+    for action in actions {
+        perform_action(action);
+    }
+}
+
+extern "C" fn pin_entry(_this: &Object, _cmd: Sel, item: id) {
+    unsafe {
+        let index: i64 = msg_send![item, tag];
+        if let Err(e) = HISTORY.lock().unwrap().pin(index as usize) {
+            info!("Failed to save history: {}", e);
+        }
+    }
+    refresh_history();
+}
+
+extern "C" fn unpin_entry(_this: &Object, _cmd: Sel, item: id) {
+    unsafe {
+        let index: i64 = msg_send![item, tag];
+        if let Err(e) = HISTORY.lock().unwrap().unpin(index as usize) {
+            info!("Failed to save history: {}", e);
+        }
+    }
+    refresh_history();
+}
+
+extern "C" fn clear_history(_this: &Object, _cmd: Sel, _sender: id) {
+    if let Err(e) = HISTORY.lock().unwrap().clear() {
+        info!("Failed to save history: {}", e);
+    }
+    refresh_history();
+}
+
+extern "C" fn undo_replace(_this: &Object, _cmd: Sel, _sender: id) {
+    clipboard::undo_replace();
+}
+
+extern "C" fn export_misses(_this: &Object, _cmd: Sel, _sender: id) {
+    unsafe {
+        // Ask where to save the CSV skeleton
+        let panel: id = msg_send![class!(NSSavePanel), savePanel];
+        let title = NSString::alloc(nil).init_str("Export Misses");
+        let _: () = msg_send![panel, setTitle:title];
+        let file_name = NSString::alloc(nil).init_str("missing.csv");
+        let _: () = msg_send![panel, setNameFieldStringValue:file_name];
+
+        let app: id = msg_send![class!(NSApplication), sharedApplication];
+        let _: () = msg_send![app, activateIgnoringOtherApps:YES];
+        let response: i64 = msg_send![panel, runModal];
+        if response != 1 {
+            // NSModalResponseCancel
+            return;
+        }
+
+        let url: id = msg_send![panel, URL];
+        let path: id = msg_send![url, path];
+        let c_str = NSString::UTF8String(path);
+        if !c_str.is_null() {
+            let path = std::ffi::CStr::from_ptr(c_str)
+                .to_string_lossy()
+                .into_owned();
+            clipboard::export_misses(Path::new(&path));
+        }
+    }
+}
+
+extern "C" fn show_log(_this: &Object, _cmd: Sel, _sender: id) {
+    let path = match logger::log_path() {
+        Some(path) if path.exists() => path,
+        _ => {
+            notification::show_notification(
+                "No log yet",
+                "Nothing has been logged to a file; check the [log] sinks in config.toml",
+            );
+            return;
+        }
+    };
+    unsafe {
+        // Opens in Console, or whichever app the user has chosen for logs
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let ns_path = NSString::alloc(nil).init_str(&path.to_string_lossy());
+        let opened: BOOL = msg_send![workspace, openFile: ns_path];
+        if opened == NO {
+            info!("Failed to open the log file {:?}", path);
+        }
+    }
+}
+
+extern "C" fn validate_menu_item(_this: &Object, _cmd: Sel, item: id) -> BOOL {
+    unsafe {
+        // Undo Replace is only available while there is something to undo
+        let action: Sel = msg_send![item, action];
+        if action == sel!(undoReplace:) && !clipboard::can_undo_replace() {
+            NO
+        } else {
+            YES
+        }
+    }
+}
+
 extern "C" fn application_will_terminate(_this: &Object, _cmd: Sel, _notification: id) {
-    info!("Application will terminate – cleaning up if necessary.");
+    info!("Application will terminate – saving history.");
+    if let Err(e) = HISTORY.lock().unwrap().save() {
+        info!("Failed to save history: {}", e);
+    }
 }
 
 pub fn create_menu(handler: id) -> id {
@@ -161,6 +346,46 @@ pub fn create_menu(handler: id) -> id {
         search_item.setTarget_(handler);
         menu.addItem_(search_item);
 
+        // Add Undo Replace item, enabled by validateMenuItem:
+        let undo_title = NSString::alloc(nil).init_str("Undo Replace");
+        let undo_item = NSMenuItem::alloc(nil).initWithTitle_action_keyEquivalent_(
+            undo_title,
+            sel!(undoReplace:),
+            NSString::alloc(nil).init_str("z"),
+        );
+        undo_item.setTarget_(handler);
+        menu.addItem_(undo_item);
+
+        // Add Export Misses item
+        let export_title = NSString::alloc(nil).init_str("Export Misses…");
+        let export_item = NSMenuItem::alloc(nil).initWithTitle_action_keyEquivalent_(
+            export_title,
+            sel!(exportMisses:),
+            NSString::alloc(nil).init_str(""),
+        );
+        export_item.setTarget_(handler);
+        menu.addItem_(export_item);
+
+        // Add Clear History item
+        let clear_title = NSString::alloc(nil).init_str("Clear History");
+        let clear_item = NSMenuItem::alloc(nil).initWithTitle_action_keyEquivalent_(
+            clear_title,
+            sel!(clearHistory:),
+            NSString::alloc(nil).init_str(""),
+        );
+        clear_item.setTarget_(handler);
+        menu.addItem_(clear_item);
+
+        // Add Show Log item
+        let log_title = NSString::alloc(nil).init_str("Show Log");
+        let log_item = NSMenuItem::alloc(nil).initWithTitle_action_keyEquivalent_(
+            log_title,
+            sel!(showLog:),
+            NSString::alloc(nil).init_str(""),
+        );
+        log_item.setTarget_(handler);
+        menu.addItem_(log_item);
+
         // Add Help item
         let help_title = NSString::alloc(nil).init_str("Help");
         let help_item = NSMenuItem::alloc(nil).initWithTitle_action_keyEquivalent_(
@@ -188,61 +413,147 @@ pub fn create_menu(handler: id) -> id {
 
         menu.addItem_(quit_item);
 
+        // Restore the saved history, newest at the top
+        render_history(menu, handler);
+
         menu
     }
 }
 
-pub fn add_menu_item(source: &str, target: &str) {
+/// Shortens text for display in the menu; the full text is kept as the
+/// item's represented object.
+fn display_title(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= MAX_TITLE_CHARS {
+        return text;
+    }
+    let mut title: String = text.chars().take(MAX_TITLE_CHARS - 1).collect();
+    title.push('…');
+    title
+}
+
+/// Builds a menu item that copies `text` when chosen, showing `title`.
+unsafe fn copy_item(title: &str, text: &str, handler: id) -> id {
     unsafe {
-        if let Some(menu) = MENU {
-            if let Some(handler) = HANDLER {
-                // Create parent menu item with source text
-                let source_title = NSString::alloc(nil).init_str(source);
-                let source_item = NSMenuItem::alloc(nil).initWithTitle_action_keyEquivalent_(
-                    source_title,
-                    sel!(doAction:),
-                    NSString::alloc(nil).init_str(""),
-                );
-
-                // Enable the menu item and set its action
-                let _: () = msg_send![source_item, setEnabled:YES];
-                let _: () = msg_send![source_item, setTarget:handler];
-                let _: () = msg_send![source_item, setAction:sel!(doAction:)];
-
-                // Create submenu for the translation
-                let submenu = NSMenu::new(nil).autorelease();
-
-                // Create translation menu item
-                let target_title = NSString::alloc(nil).init_str(target);
-                let target_item = NSMenuItem::alloc(nil).initWithTitle_action_keyEquivalent_(
-                    target_title,
-                    sel!(doAction:),
-                    NSString::alloc(nil).init_str(""),
-                );
-
-                // Enable the submenu item and set its action
-                let _: () = msg_send![target_item, setEnabled:YES];
-                let _: () = msg_send![target_item, setTarget:handler];
-                let _: () = msg_send![target_item, setAction:sel!(doAction:)];
-
-                // Add translation to submenu
-                submenu.addItem_(target_item);
-
-                // Set submenu to parent item
-                let _: () = msg_send![source_item, setSubmenu:submenu];
-
-                // Insert at the top (index 0), before the Quit item
-                let _: () = msg_send![menu, insertItem:source_item atIndex:0];
-
-                // Limit the number of items (optional)
-                let count: i64 = msg_send![menu, numberOfItems];
-                if count > 10 + STATIC_ITEMS {
-                    // Keep last 10 items plus:
-                    let _: () = msg_send![menu, removeItemAtIndex:count - STATIC_ITEMS - 1];
-                }
-            }
+        let item = NSMenuItem::alloc(nil).initWithTitle_action_keyEquivalent_(
+            NSString::alloc(nil).init_str(&display_title(title)),
+            sel!(doAction:),
+            NSString::alloc(nil).init_str(""),
+        );
+        let _: () = msg_send![item, setRepresentedObject:NSString::alloc(nil).init_str(text)];
+
+        // Enable the menu item and set its action
+        let _: () = msg_send![item, setEnabled:YES];
+        let _: () = msg_send![item, setTarget:handler];
+        let _: () = msg_send![item, setAction:sel!(doAction:)];
+        item
+    }
+}
+
+/// Builds the menu item for the history entry at `index`, with a submenu of
+/// its translations.
+unsafe fn history_item(index: usize, entry: &HistoryEntry, handler: id) -> id {
+    unsafe {
+        // Choosing the entry itself copies the source text
+        let source_item = copy_item(&entry.source, &entry.source, handler);
+        let _: () = msg_send![source_item, setTag:index as i64];
+
+        // Create submenu for the translations; items are enabled explicitly
+        let submenu = NSMenu::new(nil).autorelease();
+        let _: () = msg_send![submenu, setAutoenablesItems:NO];
+
+        for translation in &entry.translations {
+            // Label each translation with its target language
+            let title = format!("{}: {}", translation.target_name, translation.value);
+            let target_item = copy_item(&title, &translation.value, handler);
+            let _: () = msg_send![target_item, setTag:index as i64];
+            submenu.addItem_(target_item);
+        }
+
+        // Show where the mapping came from, so bad entries can be tracked down
+        let location_title = NSString::alloc(nil).init_str(&format!("from {}", entry.location));
+        let location_item = NSMenuItem::alloc(nil).initWithTitle_action_keyEquivalent_(
+            location_title,
+            sel!(doAction:),
+            NSString::alloc(nil).init_str(""),
+        );
+        let _: () = msg_send![location_item, setEnabled:NO];
+
+        // Pinned entries can be unpinned and vice versa
+        let (pin_title, pin_action) = if entry.pinned {
+            ("Unpin", sel!(unpinEntry:))
+        } else {
+            ("Pin", sel!(pinEntry:))
+        };
+        let pin_item = NSMenuItem::alloc(nil).initWithTitle_action_keyEquivalent_(
+            NSString::alloc(nil).init_str(pin_title),
+            pin_action,
+            NSString::alloc(nil).init_str(""),
+        );
+        let _: () = msg_send![pin_item, setTag:index as i64];
+        let _: () = msg_send![pin_item, setEnabled:YES];
+        let _: () = msg_send![pin_item, setTarget:handler];
+
+        submenu.addItem_(NSMenuItem::separatorItem(nil));
+        submenu.addItem_(pin_item);
+        submenu.addItem_(location_item);
+
+        // Set submenu to parent item
+        let _: () = msg_send![source_item, setSubmenu:submenu];
+
+        source_item
+    }
+}
+
+/// Replaces the history items above the static items with the current
+/// contents of [`HISTORY`]: the pinned entries, then the recent ones, newest
+/// first.
+unsafe fn render_history(menu: id, handler: id) {
+    unsafe {
+        let count: i64 = msg_send![menu, numberOfItems];
+        for _ in 0..count - STATIC_ITEMS {
+            let _: () = msg_send![menu, removeItemAtIndex:0];
+        }
+
+        let history = HISTORY.lock().unwrap();
+        let mut position: i64 = 0;
+        for (index, entry) in history.pinned() {
+            let item = history_item(index, entry, handler);
+            let _: () = msg_send![menu, insertItem:item atIndex:position];
+            position += 1;
+        }
+        // Separate the pinned section from the recent lookups
+        if position > 0 && history.recent().next().is_some() {
+            let _: () = msg_send![menu, insertItem:NSMenuItem::separatorItem(nil) atIndex:position];
+            position += 1;
+        }
+        for (index, entry) in history.recent() {
+            let item = history_item(index, entry, handler);
+            let _: () = msg_send![menu, insertItem:item atIndex:position];
+            position += 1;
+        }
+    }
+}
+
+/// Redraws the history part of the menu after [`HISTORY`] has changed.
+fn refresh_history() {
+    unsafe {
+        if let (Some(menu), Some(handler)) = (MENU, HANDLER) {
+            render_history(menu, handler);
+        }
+    }
+}
+
+/// Records a lookup in the history and shows it at the top of the menu.
+pub fn add_history_entry(entry: HistoryEntry) {
+    {
+        let mut history = HISTORY.lock().unwrap();
+        history.set_max_len(CONFIG.read().unwrap().history.size);
+        if let Err(e) = history.push(entry) {
+            info!("Failed to save history: {}", e);
         }
     }
+    refresh_history();
 }
 
 pub fn create_status_item(handler: id) -> id {
@@ -1,20 +1,219 @@
+// src/notification.rs
+//
+// Notifications for lookups and errors. The notifiers are chosen when the
+// first notification is shown: the desktop's own, then stdout as a last
+// resort for terminals and headless sessions.
+use gofer_core::data::Mapping;
+use gofer_core::notifier::{self, ActionHandler, FallbackNotifier, Notifier, StdoutNotifier};
 use log::info;
+use std::sync::Mutex;
+
+#[cfg(target_os = "macos")]
+use gofer_core::notifier::{Button, NotifyError};
+#[cfg(target_os = "macos")]
 use mac_notification_sys::*;
+#[cfg(target_os = "macos")]
 use std::sync::Once;
+#[cfg(target_os = "macos")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Only the menu bar app has a search window to open
+const HAS_SEARCH_WINDOW: bool = cfg!(target_os = "macos");
+
+lazy_static::lazy_static! {
+    static ref NOTIFIER: Mutex<FallbackNotifier> = Mutex::new(default_notifier());
+    // Where the buttons of lookup notifications lead; without one, lookups
+    // are shown without buttons
+    static ref ACTION_HANDLER: Mutex<Option<ActionHandler>> = Mutex::new(None);
+}
+
+/// Sets what choosing a button on a lookup notification does.
+pub fn set_action_handler(handler: ActionHandler) {
+    *ACTION_HANDLER.lock().unwrap() = Some(handler);
+}
 
-static INIT: Once = Once::new();
+/// The notifiers available on this platform, in order of preference.
+fn default_notifier() -> FallbackNotifier {
+    let mut notifier = FallbackNotifier::default();
+    #[cfg(target_os = "macos")]
+    notifier.push(Box::new(MacNotifier::new()));
+    #[cfg(target_os = "linux")]
+    match crate::linux::DbusNotifier::connect(None) {
+        Ok(dbus) => notifier.push(Box::new(dbus)),
+        Err(e) => info!("{}", e),
+    }
+    notifier.push(Box::new(StdoutNotifier));
+    info!("Notifying through {}", notifier.names().join(", then "));
+    notifier
+}
 
 pub fn show_notification(title: &str, message: &str) {
-    // Initialize only once
-    INIT.call_once(|| {
-        let bundle = get_bundle_identifier_or_default("com.1000ants.gofer2");
-        if let Err(e) = set_application(&bundle) {
-            info!("Failed to set application bundle: {:?}", e);
+    match NOTIFIER.lock().unwrap().notify(title, message) {
+        Ok(()) => info!("Notification sent successfully"),
+        Err(e) => info!("Failed to send notification: {}", e),
+    }
+}
+
+/// Reports that `text` has no mapping, with a notification if `notify` is
+/// set and otherwise only in the log.
+pub fn show_miss(text: &str, notify: bool) {
+    if notify {
+        show_notification(
+            "No mapping found",
+            &format!("No target text found for: {}", text),
+        );
+    } else {
+        info!("No mapping for {} characters", text.chars().count());
+    }
+}
+
+/// Shows the translations of `text`, found in `candidates` in order of
+/// precedence.
+pub fn show_lookup(text: &str, candidates: &[&Mapping]) {
+    let Some(mapping) = candidates.first() else {
+        return;
+    };
+    let title = format!(
+        "{} to {}",
+        mapping.source_name,
+        mapping.target_names().join(", ")
+    );
+    let message = format!(
+        "{} → {}{}",
+        text,
+        format_translations(mapping),
+        format_more_candidates(candidates.len())
+    );
+
+    let Some(on_action) = ACTION_HANDLER.lock().unwrap().clone() else {
+        show_notification(&title, &message);
+        return;
+    };
+    let buttons = notifier::lookup_buttons(text, &mapping.translations[0].value, HAS_SEARCH_WINDOW);
+    let result = NOTIFIER
+        .lock()
+        .unwrap()
+        .notify_with_buttons(&title, &message, &buttons, on_action);
+    match result {
+        Ok(()) => info!("Notification sent successfully"),
+        Err(e) => info!("Failed to send notification: {}", e),
+    }
+}
+
+/// Formats the translations for a notification: just the value when there is
+/// one target, otherwise every value labelled with its target name.
+fn format_translations(mapping: &Mapping) -> String {
+    match mapping.translations.as_slice() {
+        [translation] => translation.value.clone(),
+        translations => translations
+            .iter()
+            .map(|t| format!("{} ({})", t.value, t.target_name))
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+/// Notes how many other files also map the text, e.g. " (+2 more)".
+fn format_more_candidates(candidate_count: usize) -> String {
+    match candidate_count {
+        0 | 1 => String::new(),
+        n => format!(" (+{} more)", n - 1),
+    }
+}
+
+/// Notifications with buttons waiting for an answer, each on its own thread.
+#[cfg(target_os = "macos")]
+static WAITING: AtomicUsize = AtomicUsize::new(0);
+
+/// How many notifications with buttons may wait for an answer at once.
+#[cfg(target_os = "macos")]
+const MAX_WAITING: usize = 4;
+
+/// Notification Center, through mac-notification-sys.
+#[cfg(target_os = "macos")]
+pub struct MacNotifier;
+
+#[cfg(target_os = "macos")]
+impl MacNotifier {
+    pub fn new() -> Self {
+        // The bundle can only be set once per process
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            let bundle = get_bundle_identifier_or_default("com.1000ants.gofer2");
+            if let Err(e) = set_application(&bundle) {
+                info!("Failed to set application bundle: {:?}", e);
+            }
+        });
+        MacNotifier
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Notifier for MacNotifier {
+    fn name(&self) -> &'static str {
+        "Notification Center"
+    }
+
+    fn notify(&mut self, title: &str, message: &str) -> Result<(), NotifyError> {
+        send_notification("Gofer2", Some(title), message, None)
+            .map(|_| ())
+            .map_err(|e| NotifyError::new(self.name(), format!("{:?}", e)))
+    }
+
+    /// Shows the buttons in an "Actions" drop-down. Sending blocks until the
+    /// notification is answered, so it happens on a thread of its own and
+    /// this returns straight away; if delivery fails, that thread shows the
+    /// notification without buttons instead. Once `MAX_WAITING`
+    /// notifications are unanswered, new ones are shown without buttons.
+    fn notify_with_buttons(
+        &mut self,
+        title: &str,
+        message: &str,
+        buttons: &[Button],
+        on_action: ActionHandler,
+    ) -> Result<(), NotifyError> {
+        if WAITING.fetch_add(1, Ordering::SeqCst) >= MAX_WAITING {
+            WAITING.fetch_sub(1, Ordering::SeqCst);
+            info!("Too many unanswered notifications, showing this one without buttons");
+            return self.notify(title, message);
         }
-    });
 
-    match send_notification("Gofer2", Some(title), message, None) {
-        Ok(_) => info!("Notification sent successfully"),
-        Err(e) => info!("Failed to send notification: {:?}", e),
+        let title = title.to_string();
+        let message = message.to_string();
+        let buttons = buttons.to_vec();
+        std::thread::spawn(move || {
+            let labels: Vec<&str> = buttons.iter().map(|b| b.label).collect();
+            let response = Notification::new()
+                .title("Gofer2")
+                .subtitle(&title)
+                .message(&message)
+                .main_button(MainButton::DropdownActions("Actions", &labels))
+                .close_button("Close")
+                .send();
+            WAITING.fetch_sub(1, Ordering::SeqCst);
+            match response {
+                Ok(NotificationResponse::ActionButton(label)) => {
+                    if let Some(button) = buttons.iter().find(|b| b.label == label) {
+                        on_action(button.action.clone());
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    info!("Failed to send notification with buttons: {:?}", e);
+                    show_without_buttons(&title, &message);
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Shows a notification whose buttons could not be delivered, falling back
+/// to stdout like [`FallbackNotifier`] would.
+#[cfg(target_os = "macos")]
+fn show_without_buttons(title: &str, message: &str) {
+    if let Err(e) = MacNotifier.notify(title, message) {
+        info!("{}", e);
+        let _ = StdoutNotifier.notify(title, message);
     }
 }
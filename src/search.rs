@@ -1,26 +1,62 @@
 // src/search.rs
-use cocoa::appkit::{NSApp, NSWindowStyleMask};
+use cocoa::appkit::NSWindowStyleMask;
 use cocoa::base::{NO, YES, id, nil};
 use cocoa::foundation::{NSPoint, NSRect, NSSize, NSString};
-use fuzzy_matcher::FuzzyMatcher;
-use fuzzy_matcher::skim::SkimMatcherV2;
+use gofer_core::search::{SearchResult, SearchWorker};
 use log::info;
 use objc::declare::ClassDecl;
 use objc::runtime::{Class, Object, Sel};
 use objc::{class, msg_send, sel, sel_impl};
 use std::sync::Mutex;
 
-use crate::data;
+use crate::{CONFIG, STORE};
 
 lazy_static::lazy_static! {
     static ref SEARCH_RESULTS: Mutex<Vec<SearchResult>> = Mutex::new(Vec::new());
+    // Searches run off the main thread; results are handed back to the table view
+    static ref SEARCH_WORKER: SearchWorker = SearchWorker::spawn(show_results);
+    static ref SEARCH_TABLE: Mutex<Option<SearchTable>> = Mutex::new(None);
 }
 
-#[derive(Clone, Debug)]
-pub struct SearchResult {
-    pub source: String,
-    pub target: String,
-    pub score: i64,
+/// The table view of the most recently opened search window, retained until
+/// the window closes.
+struct SearchTable {
+    window: id,
+    table_view: id,
+}
+
+// Off the main thread the table view is only used, under the lock, to hand
+// work over to the main thread
+unsafe impl Send for SearchTable {}
+
+/// Makes `table` the one that shows results, releasing the previous one.
+fn set_search_table(table: Option<SearchTable>) {
+    let table = table.map(|table| SearchTable {
+        window: table.window,
+        table_view: unsafe { msg_send![table.table_view, retain] },
+    });
+    let previous = std::mem::replace(&mut *SEARCH_TABLE.lock().unwrap(), table);
+    if let Some(previous) = previous {
+        let _: () = unsafe { msg_send![previous.table_view, release] };
+    }
+}
+
+/// Called on the search worker thread with the results of the latest query.
+fn show_results(query: &str, results: Vec<SearchResult>) {
+    info!("Found {} results for {:?}", results.len(), query);
+    *SEARCH_RESULTS.lock().unwrap() = results;
+
+    // AppKit views may only be touched on the main thread, which retains
+    // the table view until it has been reloaded
+    if let Some(table) = &*SEARCH_TABLE.lock().unwrap() {
+        unsafe {
+            let _: () = msg_send![table.table_view,
+                performSelectorOnMainThread: sel!(reloadData)
+                withObject: nil
+                waitUntilDone: NO
+            ];
+        }
+    }
 }
 
 // Register our delegate classes
@@ -46,10 +82,30 @@ pub fn register_search_delegates() -> *const Class {
             table_view_value as extern "C" fn(&Object, Sel, id, id, i64) -> id,
         );
 
+        // Stop showing results in a window that is closing
+        decl.add_method(
+            sel!(windowWillClose:),
+            window_will_close as extern "C" fn(&Object, Sel, id),
+        );
+
         decl.register()
     }
 }
 
+extern "C" fn window_will_close(_this: &Object, _cmd: Sel, notification: id) {
+    unsafe {
+        let window: id = msg_send![notification, object];
+        let is_current = SEARCH_TABLE
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|table| table.window == window);
+        if is_current {
+            set_search_table(None);
+        }
+    }
+}
+
 // Add these methods to handle the table view data
 extern "C" fn number_of_rows(_this: &Object, _cmd: Sel, _table_view: id) -> i64 {
     SEARCH_RESULTS.lock().unwrap().len() as i64
@@ -72,13 +128,13 @@ extern "C" fn table_view_value(
             if !id_str.is_null() {
                 let column_id = std::ffi::CStr::from_ptr(id_str).to_string_lossy();
 
-                let value = if column_id == "source" {
-                    &result.source
-                } else {
-                    &result.target
+                let value = match column_id.as_ref() {
+                    "source" => result.source.clone(),
+                    "location" => result.location(),
+                    _ => result.target.clone(),
                 };
 
-                return NSString::alloc(nil).init_str(value);
+                return NSString::alloc(nil).init_str(&value);
             }
         }
         nil
@@ -86,10 +142,16 @@ extern "C" fn table_view_value(
 }
 
 pub extern "C" fn show_search_window(_this: &Object, _cmd: Sel, _sender: id) {
+    open_search_window("");
+}
+
+/// Opens a search window, searching for `query` straight away unless it is
+/// empty.
+pub fn open_search_window(query: &str) {
     unsafe {
         // Create window
         let window: id = msg_send![class!(NSWindow), alloc];
-        let frame = NSRect::new(NSPoint::new(0., 0.), NSSize::new(400., 300.));
+        let frame = NSRect::new(NSPoint::new(0., 0.), NSSize::new(520., 300.));
         let style_mask = NSWindowStyleMask::NSTitledWindowMask
             | NSWindowStyleMask::NSClosableWindowMask
             | NSWindowStyleMask::NSMiniaturizableWindowMask;
@@ -109,22 +171,24 @@ pub extern "C" fn show_search_window(_this: &Object, _cmd: Sel, _sender: id) {
         let _: () = msg_send![window, setLevel: 3];
 
         // Create text field
-        let text_frame = NSRect::new(NSPoint::new(20., 260.), NSSize::new(360., 25.));
+        let text_frame = NSRect::new(NSPoint::new(20., 260.), NSSize::new(480., 25.));
         let text_field: id = msg_send![class!(NSTextField), alloc];
         let text_field: id = msg_send![text_field, initWithFrame:text_frame];
 
         // Set up text field delegate
         let text_delegate: id = msg_send![class!(SearchDelegate), new];
         let _: () = msg_send![text_field, setDelegate:text_delegate];
+        let _: () = msg_send![window, setDelegate:text_delegate];
 
         // Create scroll view
-        let scroll_frame = NSRect::new(NSPoint::new(20., 20.), NSSize::new(360., 220.));
+        let scroll_frame = NSRect::new(NSPoint::new(20., 20.), NSSize::new(480., 220.));
         let scroll_view: id = msg_send![class!(NSScrollView), alloc];
         let scroll_view: id = msg_send![scroll_view, initWithFrame:scroll_frame];
 
         // Create table view
         let table_view: id = msg_send![class!(NSTableView), alloc];
         let table_view: id = msg_send![table_view, initWithFrame:scroll_frame];
+        set_search_table(Some(SearchTable { window, table_view }));
 
         // Set the delegate (which is also our data source)
         let delegate: id = msg_send![class!(SearchDelegate), new];
@@ -152,6 +216,16 @@ pub extern "C" fn show_search_window(_this: &Object, _cmd: Sel, _sender: id) {
         let _: () = msg_send![column2, setTitle:NSString::alloc(nil).init_str("Target")];
         let _: () = msg_send![table_view, addTableColumn:column2];
 
+        let column3: id = msg_send![class!(NSTableColumn), alloc];
+        let column3: id =
+            msg_send![column3, initWithIdentifier:NSString::alloc(nil).init_str("location")];
+        let _: () = msg_send![column3, setWidth:120.0];
+        let _: () = msg_send![column3, setMinWidth:50.0];
+        let _: () = msg_send![column3, setMaxWidth:1000.0];
+        let _: () = msg_send![column3, setResizingMask:2]; // NSTableColumnUserResizingMask
+        let _: () = msg_send![column3, setTitle:NSString::alloc(nil).init_str("From")];
+        let _: () = msg_send![table_view, addTableColumn:column3];
+
         // After adding columns, set up auto-resizing
         let _: () = msg_send![table_view, setColumnAutoresizingStyle:1]; // NSTableViewUniformColumnAutoresizingStyle
 
@@ -173,6 +247,12 @@ pub extern "C" fn show_search_window(_this: &Object, _cmd: Sel, _sender: id) {
         let _: () = msg_send![content_view, addSubview:text_field];
         let _: () = msg_send![content_view, addSubview:scroll_view];
 
+        if !query.is_empty() {
+            let _: () = msg_send![text_field, setStringValue:NSString::alloc(nil).init_str(query)];
+            let limit = CONFIG.read().unwrap().search.limit;
+            SEARCH_WORKER.search(STORE.current(), query, limit);
+        }
+
         // Activate the app and bring window to front
         let app: id = msg_send![class!(NSApplication), sharedApplication];
         let _: () = msg_send![app, activateIgnoringOtherApps:YES];
@@ -192,86 +272,8 @@ extern "C" fn text_did_change(_this: &Object, _cmd: Sel, notification: id) {
                 .into_owned();
 
             info!("Search query: {}", query);
-            if let Some(results) = search_mappings(&query) {
-                info!("Found {} results", results.len());
-                *SEARCH_RESULTS.lock().unwrap() = results;
-
-                // Find and reload the table view
-                if let Some(window) = find_window_with_title("Gofer2 Search") {
-                    let content_view: id = msg_send![window, contentView];
-                    let subviews: id = msg_send![content_view, subviews];
-                    let count: usize = msg_send![subviews, count];
-
-                    for i in 0..count {
-                        let view: id = msg_send![subviews, objectAtIndex:i];
-                        if msg_send![view, isKindOfClass:class!(NSScrollView)] {
-                            let doc_view: id = msg_send![view, documentView];
-                            if msg_send![doc_view, isKindOfClass:class!(NSTableView)] {
-                                info!("Reloading table view");
-                                let _: () = msg_send![doc_view, reloadData];
-                                break;
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-}
-
-fn search_mappings(query: &str) -> Option<Vec<SearchResult>> {
-    if query.is_empty() {
-        return Some(Vec::new());
-    }
-
-    let matcher = SkimMatcherV2::default();
-    let mut results = Vec::new();
-
-    if let Some(mappings) = data::get_all_mappings() {
-        for (source, mapping) in mappings {
-            // Search in source
-            if let Some(score) = matcher.fuzzy_match(&source, query) {
-                results.push(SearchResult {
-                    source: source.clone(),
-                    target: mapping.value.clone(),
-                    score,
-                });
-            }
-            // Search in target
-            if let Some(score) = matcher.fuzzy_match(&mapping.value, query) {
-                results.push(SearchResult {
-                    source: source.clone(),
-                    target: mapping.value.clone(),
-                    score,
-                });
-            }
-
-            // Don't go over 10 results to save time
-            if results.len() > 10 {
-                break;
-            }
-        }
-    }
-
-    results.sort_by(|a, b| b.score.cmp(&a.score));
-    results.dedup_by(|a, b| a.source == b.source && a.target == b.target);
-    Some(results)
-}
-
-unsafe fn find_window_with_title(title: &str) -> Option<id> {
-    let windows: id = unsafe { msg_send![NSApp(), windows] };
-    let count: usize = unsafe { msg_send![windows, count] };
-
-    for i in 0..count {
-        let window: id = unsafe { msg_send![windows, objectAtIndex:i] };
-        let window_title: id = unsafe { msg_send![window, title] };
-        let title_str = unsafe { NSString::UTF8String(window_title) };
-        if !title_str.is_null() {
-            let window_title = unsafe { std::ffi::CStr::from_ptr(title_str).to_string_lossy() };
-            if window_title == title {
-                return Some(window);
-            }
+            let limit = CONFIG.read().unwrap().search.limit;
+            SEARCH_WORKER.search(STORE.current(), &query, limit);
         }
     }
-    None
 }